@@ -1,25 +1,705 @@
-use clap::Parser;
-use geo_git::{geo::color::ColorType, gui, public::parse};
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use clap::{builder::PossibleValuesParser, Parser, Subcommand, ValueEnum};
+use geo_git::{
+    analysis::diff::ChangeKind,
+    db::{
+        error::{ConflictPolicy, DbError},
+        r#impl::Db,
+        version_controller::VersionId,
+    },
+    geo::{
+        color::{PALETTES, PRESETS},
+        point::Point,
+        rect::Rect,
+    },
+    gui::{
+        self,
+        animate::{Animation, AnimationOptions},
+        public::{Config, Dataset},
+        render::{Layers, Picture, RenderOptions},
+    },
+    ingest::{self, Source},
+    public::{parse_with_warnings, ScriptError},
+    settings::{self, By, ColorMode, Range, Settings},
+};
+
+/// Success, and nothing found by `check` or `diff`.
+const OK: i32 = 0;
+/// `check` found issues, `diff` found differences, or `log` found no such shape.
+const FOUND: i32 = 1;
+/// Bad arguments, an unreadable or malformed script, an unknown version, or a failed write.
+const FAILED: i32 = 2;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SliceFormat {
+    Script,
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum HistoryFormat {
+    Script,
+    Json,
+}
+
 #[derive(Debug, Parser)]
-#[clap(version)]
+#[clap(
+    version,
+    after_help = "Defaults are read from the user-level config file, `geo_git/config.toml` in the config \
+                  directory, then from the nearest `geo_git.toml` or `geo_git.json`, then from `--config`.\n\n\
+                  Exit status: 0 on success, 1 when `check` finds issues, `diff` finds differences \
+                  or `log` finds no such shape, 2 on errors."
+)]
 struct Opt {
-    /// Path to script-file of geom.
-    #[clap(short, long)]
-    file: String,
+    /// Path to script-file of geom. Scripts after the first are laid over it as datasets.
+    #[clap(short, long, global = true)]
+    file: Vec<PathBuf>,
+    /// Config file read after the user-level and project-local ones, and written by "save view" in the GUI.
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+    /// How shapes are colored, `gradient` by default.
+    #[clap(long, value_enum, global = true)]
+    color: Option<ColorMode>,
+    /// Gradient preset, `magma` by default, or `rd_bu` with `--diverging`.
+    #[clap(long, value_parser = PossibleValuesParser::new(PRESETS), global = true)]
+    preset: Option<String>,
+    /// How the range of gradient values is found, `global` by default.
+    #[clap(long, value_enum, global = true)]
+    range: Option<Range>,
+    /// Lower bound of a fixed range, 0 by default.
+    #[clap(long, allow_hyphen_values = true, global = true)]
+    min: Option<f32>,
+    /// Upper bound of a fixed range, 3000 by default.
+    #[clap(long, allow_hyphen_values = true, global = true)]
+    max: Option<f32>,
+    /// Lower and upper quantile of a percentile range, 0.02 and 0.98 by default.
+    #[clap(long, num_args = 2, value_names = ["LOW", "HIGH"], global = true)]
+    percentile: Option<Vec<f32>>,
+    /// Spread the values over the gradient on a log scale.
+    #[clap(long, global = true, overrides_with = "no_log")]
+    log: bool,
+    /// Spread the values linearly, even if the config files ask for `log`.
+    #[clap(long, global = true, overrides_with = "log")]
+    no_log: bool,
+    /// Center the gradient on zero, with a diverging color scheme.
+    #[clap(long, global = true, overrides_with = "no_diverging")]
+    diverging: bool,
+    /// Do not center the gradient on zero, even if the config files ask for `diverging`.
+    #[clap(long, global = true, overrides_with = "diverging")]
+    no_diverging: bool,
+    /// Value channel colored by the gradient, or grouped by `--by channel`.
+    #[clap(long, global = true)]
+    channel: Option<String>,
+    /// What shapes are grouped by in categorical coloring, `name` by default.
+    #[clap(long, value_enum, global = true)]
+    by: Option<By>,
+    /// Number of name components of `--by prefix`.
+    #[clap(long, global = true)]
+    depth: Option<usize>,
+    /// Palette of categorical coloring, `tableau10` by default.
+    #[clap(long, value_parser = PossibleValuesParser::new(PALETTES.iter().map(|p| p.name)), global = true)]
+    palette: Option<String>,
+    /// Report the displacement of rects between two versions instead of opening the GUI.
+    #[clap(long, num_args = 2, value_names = ["FROM", "TO"])]
+    displacement: Option<Vec<u32>>,
+    /// Number of largest movers listed by `--displacement`.
+    #[clap(long, default_value_t = 10)]
+    top: usize,
+    /// Keep reading what is appended to the first `--file` while the GUI runs, `tail -f` style.
+    #[clap(long, conflicts_with = "listen")]
+    follow: bool,
+    /// Add the commits streamed by other tools while the GUI runs: `-` for stdin,
+    /// `unix:PATH` for a Unix domain socket or `tcp:ADDR` for a TCP socket.
+    #[clap(long, value_name = "SOURCE")]
+    listen: Option<Source>,
+    /// Script to open in a pane of its own, next to `--file`.
+    #[clap(long)]
+    compare: Vec<PathBuf>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Open the GUI, the default.
+    View,
+    /// Parse the script, report every action that does not apply, and exit with 1 if there is any.
+    Check,
+    /// Print the shape counts, area, utilization, overlap and changes of every version.
+    Stats {
+        #[clap(long)]
+        json: bool,
+    },
+    /// List the shapes added, deleted and modified between two versions, and exit with 1 if there is any.
+    Diff {
+        from: u32,
+        to: u32,
+        #[clap(long)]
+        json: bool,
+    },
+    /// Print every change of a shape, with the messages of the commits making them.
+    Log { name: String },
+    /// Write the shapes of a version.
+    Slice {
+        version: u32,
+        #[clap(long, value_enum, default_value_t = SliceFormat::Script)]
+        format: SliceFormat,
+        /// File to write, stdout by default.
+        #[clap(long)]
+        out: Option<PathBuf>,
+    },
+    /// Write the whole history as a normalized script, or as JSON.
+    Convert {
+        /// The format of the extension of `--out` by default, or a script.
+        #[clap(long, value_enum)]
+        format: Option<HistoryFormat>,
+        /// File to write, stdout by default.
+        #[clap(long)]
+        out: Option<PathBuf>,
+    },
+    /// Render a version to a PNG or SVG image, without opening the GUI.
+    Render {
+        /// Version to render, the last one by default.
+        #[clap(long)]
+        version: Option<u32>,
+        /// Image to write, `.png` or `.svg`.
+        #[clap(long)]
+        out: PathBuf,
+        #[clap(long, default_value_t = 1024)]
+        width: u32,
+        #[clap(long, default_value_t = 1024)]
+        height: u32,
+        /// Area of the layout to show, the bounding box of the shapes by default.
+        #[clap(long, num_args = 4, value_names = ["LLX", "LLY", "URX", "URY"], allow_hyphen_values = true)]
+        viewport: Option<Vec<f64>>,
+        /// Render the changes since this version instead of the shapes.
+        #[clap(long)]
+        diff_from: Option<u32>,
+        #[clap(long)]
+        no_rects: bool,
+        #[clap(long)]
+        no_lines: bool,
+        /// Draw the regions where rects overlap.
+        #[clap(long)]
+        overlaps: bool,
+        /// Draw the density map with this many bins along x and y.
+        #[clap(long, num_args = 2, value_names = ["NX", "NY"])]
+        density: Option<Vec<usize>>,
+    },
+    /// Play a range of versions as an animated GIF, or as a directory of PNG frames.
+    Animate {
+        /// First version shown.
+        #[clap(long, default_value_t = 1)]
+        from: u32,
+        /// Last version shown, the last one by default.
+        #[clap(long)]
+        to: Option<u32>,
+        /// Number of versions between two frames.
+        #[clap(long, default_value_t = 1)]
+        step: u32,
+        /// How long a frame is shown, in milliseconds.
+        #[clap(long, default_value_t = 200)]
+        delay: u32,
+        /// `.gif` file to write, or directory to fill with `frame_NNNNN.png`.
+        #[clap(long)]
+        out: PathBuf,
+        #[clap(long, default_value_t = 512)]
+        width: u32,
+        #[clap(long, default_value_t = 512)]
+        height: u32,
+        /// Area of the layout to show, the area every frame fits in by default.
+        #[clap(long, num_args = 4, value_names = ["LLX", "LLY", "URX", "URY"], allow_hyphen_values = true)]
+        viewport: Option<Vec<f64>>,
+        /// Leave out the version number and commit message.
+        #[clap(long)]
+        no_caption: bool,
+    },
+}
+
+fn viewport_of(v: Option<Vec<f64>>) -> Option<Rect> {
+    v.map(|v| Rect::new(Point::new(v[0], v[1]), Point::new(v[2], v[3])))
+}
+
+/// The value of a `--flag`/`--no-flag` pair, `None` if neither was given. The last one given wins.
+fn switch(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (false, false) => None,
+        (on, _) => Some(on),
+    }
+}
+
+/// The settings of the config files, overridden by the options.
+fn settings(opt: &Opt) -> Result<Settings, String> {
+    let dir = std::env::current_dir().map_err(|e| e.to_string())?;
+    let mut res = Settings::load(&dir, opt.config.as_deref())?;
+    let color = &mut res.color;
+    if let Some(mode) = opt.color {
+        color.mode = mode;
+    }
+    if opt.preset.is_some() {
+        color.preset.clone_from(&opt.preset);
+    }
+    if let Some(range) = opt.range {
+        color.range = range;
+    }
+    if let Some(min) = opt.min {
+        color.min = min;
+    }
+    if let Some(max) = opt.max {
+        color.max = max;
+    }
+    if let Some(p) = &opt.percentile {
+        color.percentile = [p[0], p[1]];
+    }
+    if let Some(log) = switch(opt.log, opt.no_log) {
+        color.log = log;
+    }
+    if let Some(diverging) = switch(opt.diverging, opt.no_diverging) {
+        color.diverging = diverging;
+    }
+    if let Some(channel) = &opt.channel {
+        color.channel.clone_from(channel);
+    }
+    if let Some(by) = opt.by {
+        color.by = by;
+    }
+    if let Some(depth) = opt.depth {
+        color.depth = depth;
+    }
+    if let Some(palette) = &opt.palette {
+        color.palette.clone_from(palette);
+    }
+    Ok(res)
+}
+
+/// Where the GUI saves its view: `--config`, or the project-local config file, or a new one
+/// in the working directory.
+fn settings_path(opt: &Opt) -> PathBuf {
+    let dir = std::env::current_dir().unwrap_or_default();
+    opt.config
+        .clone()
+        .or_else(|| settings::project_file(&dir))
+        .unwrap_or_else(|| dir.join(settings::PROJECT_FILES[0]))
+}
+
+/// Why the script at `path` cannot be loaded.
+enum LoadError {
+    Read {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// The script is malformed, or one of its commits is rejected.
+    /// `at` is the line and the column of a parse error, from 1.
+    Script {
+        path: PathBuf,
+        error: ScriptError,
+        at: Option<(usize, usize)>,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Read { path, error } => write!(f, "{}: {}", path.display(), error),
+            LoadError::Script {
+                path,
+                error: ScriptError::Parse(e),
+                at: Some((line, column)),
+            } => write!(f, "{}:{}:{}: {}", path.display(), line, column, e.message),
+            LoadError::Script { path, error, .. } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl From<LoadError> for String {
+    fn from(e: LoadError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Line and column of the byte `offset` of `text`, from 1.
+fn location(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..text.floor_char_boundary(offset.min(text.len()))];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+/// Parse the script at `path`, together with the actions skipped by `cfg.policy`.
+async fn parse_file(path: &Path, cfg: Config) -> Result<(Db, Vec<DbError>), LoadError> {
+    let text = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|error| LoadError::Read {
+            path: path.to_owned(),
+            error,
+        })?;
+    parse_with_warnings(text.clone(), cfg).map_err(|error| {
+        let at = match &error {
+            ScriptError::Parse(e) => Some(location(&text, e.offset)),
+            ScriptError::Db(_) => None,
+        };
+        LoadError::Script {
+            path: path.to_owned(),
+            error,
+            at,
+        }
+    })
+}
+
+/// Parse the script at `path` with the coloring of the settings, printing the warnings.
+async fn load(path: &Path, settings: &Settings) -> Result<Db, String> {
+    let (db, warnings) = parse_file(path, settings.config()?).await?;
+    for w in warnings {
+        eprintln!("warning: {}: {}", path.display(), w);
+    }
+    Ok(db)
+}
+
+fn version_of(db: &Db, version: u32) -> Result<VersionId, String> {
+    match VersionId(version) {
+        v if v <= db.version => Ok(v),
+        v => Err(format!(
+            "version {} does not exist, the db has {}",
+            v.0, db.version.0
+        )),
+    }
+}
+
+/// Write `text` to `out`, or to stdout.
+fn emit(out: Option<&Path>, text: &str) -> Result<(), String> {
+    match out {
+        Some(path) => std::fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e)),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+/// Parse every script, reporting the malformed ones and the skipped actions as issues.
+/// An unreadable script is an error.
+async fn check(opt: &Opt, settings: &Settings) -> Result<i32, String> {
+    let mut issues = 0;
+    for path in opt.file.iter() {
+        let cfg = Config {
+            policy: ConflictPolicy::WarnAndSkip,
+            ..settings.config()?
+        };
+        match parse_file(path, cfg).await {
+            Ok((db, warnings)) => {
+                for w in warnings.iter() {
+                    println!("{}: {}", path.display(), w);
+                }
+                println!(
+                    "{}: {} versions, {} issues",
+                    path.display(),
+                    db.version.0,
+                    warnings.len()
+                );
+                issues += warnings.len();
+            }
+            Err(e @ LoadError::Script { .. }) => {
+                println!("{}", e);
+                issues += 1;
+            }
+            Err(e @ LoadError::Read { .. }) => return Err(e.into()),
+        }
+    }
+    Ok(if issues == 0 { OK } else { FOUND })
+}
+
+fn stats(db: &Db, json: bool) {
+    let stats = db.stats();
+    if json {
+        let rows: Vec<_> = stats
+            .iter()
+            .map(|s| {
+                serde_json::json!({
+                    "version": s.version.0,
+                    "message": db.message(s.version),
+                    "rects": s.rects,
+                    "lines": s.lines,
+                    "area": s.area,
+                    "utilization": s.utilization(),
+                    "overlap": s.overlap,
+                    "added": s.added,
+                    "modified": s.modified,
+                    "deleted": s.deleted,
+                    "displacement": s.displacement,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::Value::from(rows));
+        return;
+    }
+    println!("version\trects\tlines\tarea\tutil\toverlap\tadded\tmodified\tdeleted\tdisplacement");
+    for s in stats.iter() {
+        println!(
+            "{}\t{}\t{}\t{:.3}\t{:.3}\t{:.3}\t{}\t{}\t{}\t{:.3}",
+            s.version.0,
+            s.rects,
+            s.lines,
+            s.area,
+            s.utilization(),
+            s.overlap,
+            s.added,
+            s.modified,
+            s.deleted,
+            s.displacement
+        );
+    }
+}
+
+fn diff(db: &Db, from: VersionId, to: VersionId, json: bool) -> i32 {
+    let changes = db.diff(from, to);
+    let sign = |kind| match kind {
+        ChangeKind::Added => "+",
+        ChangeKind::Deleted => "-",
+        ChangeKind::Modified => "~",
+    };
+    if json {
+        let rows: Vec<_> = changes
+            .iter()
+            .map(|c| {
+                serde_json::json!({
+                    "name": String::from_utf8_lossy(&c.name),
+                    "shape": c.shape,
+                    "change": format!("{:?}", c.kind).to_lowercase(),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::Value::from(rows));
+    } else {
+        for c in changes.iter() {
+            println!(
+                "{} {} {}",
+                sign(c.kind),
+                c.shape,
+                String::from_utf8_lossy(&c.name)
+            );
+        }
+    }
+    if changes.is_empty() {
+        OK
+    } else {
+        FOUND
+    }
+}
+
+fn log(db: &Db, name: &str) -> i32 {
+    let entries = db.log(name.as_bytes());
+    if entries.is_empty() {
+        eprintln!("no shape named `{}`", name);
+        return FOUND;
+    }
+    for (version, action) in entries {
+        match db.message(version) {
+            Some(message) => println!("{}\t{}\t{}", version.0, action, message.replace('\n', " ")),
+            None => println!("{}\t{}", version.0, action),
+        }
+    }
+    OK
+}
+
+fn displacement(db: &Db, versions: &[u32], top: usize) -> Result<(), String> {
+    let report = db.displacement(version_of(db, versions[0])?, version_of(db, versions[1])?);
+    println!("rects: {}", report.moves.len());
+    println!("max:   {}", report.max());
+    println!("mean:  {}", report.mean());
+    println!("histogram: {:?}", report.histogram(10));
+    for d in report.top(top) {
+        println!(
+            "{}\t{}\t({}, {})",
+            String::from_utf8_lossy(&d.name),
+            d.shift.manhattan(),
+            d.shift.dx(),
+            d.shift.dy()
+        );
+    }
+    Ok(())
+}
+
+/// Open the GUI on the first `--file`, with the others and `--compare` as datasets.
+async fn view(opt: &Opt, settings: Settings) -> Result<(), String> {
+    // A followed script is read by `ingest::follow`, from its first commit on.
+    let db = match opt.file.first() {
+        Some(path) if !opt.follow => load(path, &settings).await?,
+        _ => Db::new(settings.config()?),
+    };
+    let mut datasets = vec![];
+    for (idx, path) in opt
+        .file
+        .iter()
+        .skip(1)
+        .chain(opt.compare.iter())
+        .enumerate()
+    {
+        let mut dataset = Dataset::new(
+            path.display().to_string(),
+            load(path, &settings).await?,
+            idx,
+        );
+        dataset.visible = idx + 1 < opt.file.len();
+        datasets.push(dataset);
+    }
+    let source = match (&opt.listen, opt.follow) {
+        (Some(source), _) => Some(source.clone()),
+        (None, true) => Some(Source::File(opt.file[0].clone())),
+        (None, false) => None,
+    };
+    let label = match opt.file.first() {
+        Some(path) => path.display().to_string(),
+        None => source.as_ref().unwrap().to_string(),
+    };
+    let incoming = source.map(ingest::start);
+    gui::public::run_gui(label, db, datasets, incoming, settings, settings_path(opt));
+    Ok(())
+}
+
+async fn run(opt: &Opt) -> Result<i32, String> {
+    let viewing = matches!(opt.command, None | Some(Command::View)) && opt.displacement.is_none();
+    if opt.file.is_empty() && !(viewing && opt.listen.is_some()) {
+        return Err("--file is required".to_owned());
+    }
+    let settings = settings(opt)?;
+    if viewing {
+        view(opt, settings).await?;
+        return Ok(OK);
+    }
+    if let Some(Command::Check) = opt.command {
+        return check(opt, &settings).await;
+    }
+    let db = load(&opt.file[0], &settings).await?;
+    match &opt.command {
+        None | Some(Command::View) => {
+            displacement(&db, opt.displacement.as_ref().unwrap(), opt.top)?;
+        }
+        Some(Command::Check) => unreachable!(),
+        Some(Command::Stats { json }) => stats(&db, *json),
+        Some(Command::Diff { from, to, json }) => {
+            let (from, to) = (version_of(&db, *from)?, version_of(&db, *to)?);
+            return Ok(diff(&db, from, to, *json));
+        }
+        Some(Command::Log { name }) => return Ok(log(&db, name)),
+        Some(Command::Slice {
+            version,
+            format,
+            out,
+        }) => {
+            let version = version_of(&db, *version)?;
+            let text = match format {
+                SliceFormat::Script => db.snapshot_script(version),
+                SliceFormat::Csv => db.snapshot_csv(version),
+                SliceFormat::Json => format!("{:#}\n", db.snapshot_json(version)),
+            };
+            emit(out.as_deref(), &text)?;
+        }
+        Some(Command::Convert { format, out }) => {
+            let by_extension = out
+                .as_ref()
+                .is_some_and(|p| p.extension().is_some_and(|e| e == "json"));
+            let text = match format {
+                Some(HistoryFormat::Json) => format!("{:#}\n", db.json()),
+                None if by_extension => format!("{:#}\n", db.json()),
+                _ => db.script(),
+            };
+            emit(out.as_deref(), &text)?;
+        }
+        Some(Command::Render {
+            version,
+            out,
+            width,
+            height,
+            viewport,
+            diff_from,
+            no_rects,
+            no_lines,
+            overlaps,
+            density,
+        }) => {
+            let version = match version {
+                Some(v) => version_of(&db, *v)?,
+                None => db.version,
+            };
+            let opts = RenderOptions {
+                width: *width,
+                height: *height,
+                viewport: viewport_of(viewport.clone()).or(settings.viewport()),
+                layers: Layers {
+                    rects: !no_rects,
+                    lines: !no_lines,
+                    overlaps: *overlaps || settings.layers.overlaps,
+                    density: match density {
+                        Some(d) => Some((d[0].max(1), d[1].max(1))),
+                        None => settings.layers.density.then(|| {
+                            let [nx, ny] = settings.layers.density_bins;
+                            (nx.max(1), ny.max(1))
+                        }),
+                    },
+                },
+                ..Default::default()
+            };
+            let picture = match diff_from {
+                Some(from) => Picture::diff(&db, version_of(&db, *from)?, version, &opts),
+                None => Picture::version(&db, version, &opts),
+            };
+            picture
+                .save(out)
+                .map_err(|e| format!("{}: {}", out.display(), e))?;
+        }
+        Some(Command::Animate {
+            from,
+            to,
+            step,
+            delay,
+            out,
+            width,
+            height,
+            viewport,
+            no_caption,
+        }) => {
+            let to = match to {
+                Some(v) => version_of(&db, *v)?,
+                None => db.version,
+            };
+            if VersionId(*from) > to {
+                return Err(format!("cannot play {}..={}", from, to.0));
+            }
+            let opts = AnimationOptions {
+                from: VersionId(*from),
+                to,
+                step: (*step).max(1),
+                delay: *delay,
+                caption: !no_caption,
+                render: RenderOptions {
+                    width: *width,
+                    height: *height,
+                    viewport: viewport_of(viewport.clone()).or(settings.viewport()),
+                    ..Default::default()
+                },
+            };
+            Animation::new(&db, opts)
+                .save(out)
+                .map_err(|e| format!("{}: {}", out.display(), e))?;
+        }
+    }
+    Ok(OK)
 }
 
 #[tokio::main]
 pub async fn main() {
-    println!("Hi");
     let opt = Opt::parse();
-    let str = String::from_utf8(tokio::fs::read(opt.file).await.unwrap()).unwrap();
-    let cfg = crate::gui::public::Config {
-        color: ColorType::Gradient {
-            generator: Box::new(colorgrad::preset::magma()),
-            min: 0.0,
-            max: 3000.0,
-        },
+    let code = match run(&opt).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            FAILED
+        }
     };
-    let db = parse(str, cfg);
-    gui::public::run_gui(db);
+    std::process::exit(code);
 }
@@ -0,0 +1,118 @@
+use crate::{
+    db::r#impl::{Db, SlicedDb},
+    geo::{point::Point, rect::Rect},
+};
+
+/// Occupied area fraction of every bin of a `nx` × `ny` grid laid over `die`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DensityMap {
+    pub die: Rect,
+    pub nx: usize,
+    pub ny: usize,
+    /// Row-major, `bins[iy * nx + ix]`. Overlapping rects are counted once each, so a bin may exceed 1.
+    pub bins: Vec<f64>,
+}
+
+impl DensityMap {
+    pub fn at(&self, ix: usize, iy: usize) -> f64 {
+        self.bins[iy * self.nx + ix]
+    }
+
+    pub fn bin(&self, ix: usize, iy: usize) -> Rect {
+        let w = self.die.width() / self.nx as f64;
+        let h = self.die.height() / self.ny as f64;
+        let ll = Point::new(self.die.ll.x + ix as f64 * w, self.die.ll.y + iy as f64 * h);
+        Rect::new(ll, Point::new(ll.x + w, ll.y + h))
+    }
+
+    pub fn max(&self) -> f64 {
+        self.bins.iter().copied().fold(0.0, f64::max)
+    }
+}
+
+impl SlicedDb {
+    /// Bounding box of all the rects in this version.
+    pub fn bbox(&self) -> Option<Rect> {
+        Rect::bounding(self.rects.values().map(|r| r.geo))
+    }
+
+    /// Split `die` into a `nx` × `ny` grid and compute the density of every bin.
+    /// A grid has at least one bin along each axis, a count of 0 is taken as 1.
+    pub fn density(&self, die: Rect, nx: usize, ny: usize) -> DensityMap {
+        let (nx, ny) = (nx.max(1), ny.max(1));
+        let mut map = DensityMap {
+            die,
+            nx,
+            ny,
+            bins: vec![0.0; nx * ny],
+        };
+        let w = die.width() / nx as f64;
+        let h = die.height() / ny as f64;
+        let bin_area = w * h;
+        if bin_area <= 0.0 {
+            return map;
+        }
+        let index = |v: f64, lo: f64, step: f64, n: usize| -> usize {
+            (((v - lo) / step).floor().max(0.0) as usize).min(n - 1)
+        };
+        for rect in self.rects.values() {
+            let Some(clipped) = rect.geo.intersection(&die) else {
+                continue;
+            };
+            let (x0, x1) = (
+                index(clipped.ll.x, die.ll.x, w, nx),
+                index(clipped.ur.x, die.ll.x, w, nx),
+            );
+            let (y0, y1) = (
+                index(clipped.ll.y, die.ll.y, h, ny),
+                index(clipped.ur.y, die.ll.y, h, ny),
+            );
+            for iy in y0..=y1 {
+                for ix in x0..=x1 {
+                    if let Some(covered) = map.bin(ix, iy).intersection(&clipped) {
+                        map.bins[iy * nx + ix] += covered.area() / bin_area;
+                    }
+                }
+            }
+        }
+        map
+    }
+}
+
+impl Db {
+    /// Bounding box of every rect in every version, which stays fixed while browsing versions.
+    pub fn bbox(&self) -> Option<Rect> {
+        Rect::bounding(
+            self.rects
+                .values()
+                .flat_map(|h| h.0.values().flatten().map(|r| r.geo)),
+        )
+    }
+}
+
+#[test]
+fn density_map() {
+    use crate::{db::version_controller::VersionId, gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 2 2 1 1 1 ;\nADDRECT B 2 0 3 1 1 1 1 ;\nADDRECT C 3 3 4 4 1 1 1 ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let v1 = db.slice(VersionId(1));
+    let die = v1.bbox().unwrap();
+    assert_eq!(die, Rect::new(Point::new(0.0, 0.0), Point::new(4.0, 4.0)));
+
+    let map = v1.density(die, 2, 2);
+    assert_eq!(map.bins, vec![1.0, 0.25, 0.0, 0.25]);
+    assert_eq!(map.max(), 1.0);
+    assert_eq!(
+        map.bin(1, 0),
+        Rect::new(Point::new(2.0, 0.0), Point::new(4.0, 2.0))
+    );
+
+    let map = v1.density(die, 0, 1);
+    assert_eq!((map.nx, map.ny), (1, 1));
+    assert_eq!(map.bins, vec![6.0 / 16.0]);
+}
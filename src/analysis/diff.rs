@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+
+use crate::db::{
+    r#impl::{Db, SlicedDb},
+    version_controller::VersionId,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChangeKind {
+    Added,
+    Deleted,
+    Modified,
+}
+
+/// How one shape differs between two versions.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Change {
+    pub name: Bytes,
+    /// `rect` or `line`.
+    pub shape: &'static str,
+    pub kind: ChangeKind,
+}
+
+fn changes<T: PartialEq>(
+    before: &BTreeMap<Bytes, T>,
+    after: &BTreeMap<Bytes, T>,
+    shape: &'static str,
+    res: &mut Vec<Change>,
+) {
+    let change = |name: &Bytes, kind| Change {
+        name: name.clone(),
+        shape,
+        kind,
+    };
+    for (name, a) in after.iter() {
+        match before.get(name) {
+            None => res.push(change(name, ChangeKind::Added)),
+            Some(b) if b != a => res.push(change(name, ChangeKind::Modified)),
+            Some(_) => {}
+        }
+    }
+    for name in before.keys().filter(|n| !after.contains_key(*n)) {
+        res.push(change(name, ChangeKind::Deleted));
+    }
+}
+
+impl SlicedDb {
+    /// The shapes which differ from `self` to `after`: rects, then lines, added and modified
+    /// ones in name order, then the deleted ones.
+    pub fn changes(&self, after: &SlicedDb) -> Vec<Change> {
+        let mut res = vec![];
+        changes(&self.rects, &after.rects, "rect", &mut res);
+        changes(&self.lines, &after.lines, "line", &mut res);
+        res
+    }
+}
+
+impl Db {
+    /// The shapes which differ between versions `from` and `to`, see `SlicedDb::changes`.
+    pub fn diff(&self, from: VersionId, to: VersionId) -> Vec<Change> {
+        self.slice(from).changes(&self.slice(to))
+    }
+}
+
+#[test]
+fn diff_versions() {
+    use crate::{gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 1 1 ;\nADDRECT B 1 1 2 2 ;\nADDLINE L 0 0 1 1 ;\n\
+         COMMIT\nUPDRECT A 0 0 2 2 ;\nDELRECT B ;\nADDRECT C 3 3 4 4 ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let kinds: Vec<_> = db
+        .diff(VersionId(1), VersionId(2))
+        .into_iter()
+        .map(|c| (c.name, c.kind))
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            (Bytes::from("A"), ChangeKind::Modified),
+            (Bytes::from("C"), ChangeKind::Added),
+            (Bytes::from("B"), ChangeKind::Deleted),
+        ]
+    );
+    assert!(db.diff(VersionId(2), VersionId(2)).is_empty());
+}
@@ -0,0 +1,105 @@
+use bytes::Bytes;
+
+use crate::{
+    db::{r#impl::Db, version_controller::VersionId},
+    geo::vector::Vector,
+};
+
+/// Move of the center of one rect between two versions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Displacement {
+    pub name: Bytes,
+    pub shift: Vector,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisplacementReport {
+    pub from: VersionId,
+    pub to: VersionId,
+    /// Every rect alive in both versions, moved or not, in name order.
+    pub moves: Vec<Displacement>,
+}
+
+impl DisplacementReport {
+    /// Largest Manhattan displacement.
+    pub fn max(&self) -> f64 {
+        self.moves
+            .iter()
+            .map(|d| d.shift.manhattan())
+            .fold(0.0, f64::max)
+    }
+
+    /// Mean Manhattan displacement, zero if no rect is in both versions.
+    pub fn mean(&self) -> f64 {
+        if self.moves.is_empty() {
+            return 0.0;
+        }
+        self.moves.iter().map(|d| d.shift.manhattan()).sum::<f64>() / self.moves.len() as f64
+    }
+
+    /// Count of rects per Manhattan displacement bin, `bins` bins evenly covering `0..=max`.
+    /// Empty if `bins` is 0.
+    pub fn histogram(&self, bins: usize) -> Vec<usize> {
+        if bins == 0 {
+            return vec![];
+        }
+        let mut res = vec![0; bins];
+        let max = self.max();
+        for d in self.moves.iter() {
+            let idx = if max > 0.0 {
+                ((d.shift.manhattan() / max * bins as f64) as usize).min(bins - 1)
+            } else {
+                0
+            };
+            res[idx] += 1;
+        }
+        res
+    }
+
+    /// The `n` rects with the largest Manhattan displacement, largest first.
+    pub fn top(&self, n: usize) -> Vec<&Displacement> {
+        let mut res: Vec<_> = self.moves.iter().collect();
+        res.sort_by(|a, b| b.shift.manhattan().total_cmp(&a.shift.manhattan()));
+        res.truncate(n);
+        res
+    }
+}
+
+impl Db {
+    /// Displacement of every rect alive at both `from` and `to`.
+    pub fn displacement(&self, from: VersionId, to: VersionId) -> DisplacementReport {
+        let moves = self
+            .rects
+            .iter()
+            .filter_map(|(name, history)| {
+                let before = history.query(from)?;
+                let after = history.query(to)?;
+                Some(Displacement {
+                    name: name.clone(),
+                    shift: after.geo.center() - before.geo.center(),
+                })
+            })
+            .collect();
+        DisplacementReport { from, to, moves }
+    }
+}
+
+#[test]
+fn displacement_report() {
+    use crate::{gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 2 2 1 1 1 ;\nADDRECT B 0 0 1 1 1 1 1 ;\nADDRECT C 0 0 1 1 1 1 1 ;\nCOMMIT\nUPDRECT A 3 4 5 6 ;\nUPDRECT B 1 0 2 1 ;\nDELRECT C ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let report = db.displacement(VersionId(1), VersionId(2));
+    assert_eq!(report.moves.len(), 2);
+    assert_eq!(report.max(), 7.0);
+    assert_eq!(report.mean(), 4.0);
+    assert_eq!(report.histogram(2), vec![1, 1]);
+    assert!(report.histogram(0).is_empty());
+    assert_eq!(report.top(1)[0].name, "A");
+    assert_eq!(report.top(1)[0].shift.euclidean(), 5.0);
+}
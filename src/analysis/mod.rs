@@ -0,0 +1,6 @@
+pub mod density;
+pub mod diff;
+pub mod displacement;
+pub mod overlap;
+pub mod search;
+pub mod stats;
@@ -0,0 +1,236 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ops::Bound::{Excluded, Unbounded},
+};
+
+use bytes::Bytes;
+
+use crate::{
+    db::{
+        r#impl::{Db, RectInfo, SlicedDb},
+        version_controller::VersionId,
+    },
+    geo::rect::Rect,
+};
+
+/// Two rects sharing a region of positive area.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Overlap {
+    pub a: Bytes,
+    pub b: Bytes,
+    pub region: Rect,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OverlapReport {
+    pub pairs: Vec<Overlap>,
+    /// Sum of the areas of all overlapping regions, a region covered by three rects counts thrice.
+    pub total_area: f64,
+}
+
+impl SlicedDb {
+    /// Find every pair of overlapping rects with a sweep line along x.
+    pub fn overlaps(&self) -> OverlapReport {
+        let mut rects: Vec<_> = self.rects.values().collect();
+        rects.sort_by(|a, b| a.geo.ll.x.total_cmp(&b.geo.ll.x));
+
+        let mut report = OverlapReport::default();
+        let mut active: Vec<&RectInfo> = vec![];
+        for rect in rects {
+            active.retain(|r| r.geo.ur.x > rect.geo.ll.x);
+            for other in active.iter() {
+                if !other.geo.overlaps(&rect.geo) {
+                    continue;
+                }
+                let region = other.geo.intersection(&rect.geo).unwrap();
+                report.total_area += region.area();
+                report.pairs.push(Overlap {
+                    a: other.name.clone(),
+                    b: rect.name.clone(),
+                    region,
+                });
+            }
+            active.push(rect);
+        }
+        report
+    }
+}
+
+/// Rects spanning more cells than this are kept aside and checked against every change.
+const MAX_CELLS: i64 = 64;
+
+/// The alive rects, bucketed by the cells of a uniform grid they cover, so that the rects near
+/// a given one are found without going through all of them.
+struct Grid<'a> {
+    cell: f64,
+    rects: HashMap<&'a Bytes, Rect>,
+    cells: HashMap<(i64, i64), Vec<&'a Bytes>>,
+    large: BTreeSet<&'a Bytes>,
+}
+
+impl<'a> Grid<'a> {
+    fn new(cell: f64) -> Self {
+        Self {
+            cell,
+            rects: HashMap::new(),
+            cells: HashMap::new(),
+            large: BTreeSet::new(),
+        }
+    }
+
+    /// The cells covered by `geo`, `None` if there are more than `MAX_CELLS` of them.
+    fn cells(&self, geo: &Rect) -> Option<impl Iterator<Item = (i64, i64)>> {
+        let index = |v: f64| (v / self.cell).floor() as i64;
+        let (x0, x1) = (index(geo.ll.x), index(geo.ur.x));
+        let (y0, y1) = (index(geo.ll.y), index(geo.ur.y));
+        let count = (x1 - x0 + 1).saturating_mul(y1 - y0 + 1);
+        (count <= MAX_CELLS).then(|| (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y))))
+    }
+
+    fn insert(&mut self, name: &'a Bytes, geo: Rect) {
+        match self.cells(&geo) {
+            Some(cells) => {
+                for cell in cells.collect::<Vec<_>>() {
+                    self.cells.entry(cell).or_default().push(name);
+                }
+            }
+            None => {
+                self.large.insert(name);
+            }
+        }
+        self.rects.insert(name, geo);
+    }
+
+    fn remove(&mut self, name: &Bytes) {
+        let Some(geo) = self.rects.remove(name) else {
+            return;
+        };
+        match self.cells(&geo) {
+            Some(cells) => {
+                for cell in cells.collect::<Vec<_>>() {
+                    if let Some(names) = self.cells.get_mut(&cell) {
+                        names.retain(|n| *n != name);
+                        if names.is_empty() {
+                            self.cells.remove(&cell);
+                        }
+                    }
+                }
+            }
+            None => {
+                self.large.remove(name);
+            }
+        }
+    }
+
+    /// The alive rects which overlap `geo`, with their geometry.
+    fn overlapping(&self, geo: &Rect) -> Vec<(&'a Bytes, Rect)> {
+        let mut names: Vec<&'a Bytes> = match self.cells(geo) {
+            Some(cells) => cells
+                .filter_map(|cell| self.cells.get(&cell))
+                .flatten()
+                .chain(self.large.iter())
+                .copied()
+                .collect(),
+            None => self.rects.keys().copied().collect(),
+        };
+        names.sort();
+        names.dedup();
+        names
+            .into_iter()
+            .map(|name| (name, self.rects[name]))
+            .filter(|(_, other)| geo.overlaps(other))
+            .collect()
+    }
+}
+
+impl Db {
+    /// Total overlap area of every version, from the first commit to the head.
+    /// The overlapping pairs are carried from version to version, and only the rects a commit
+    /// changes are checked again, against their neighbours on a grid sized after the average rect.
+    pub fn overlap_curve(&self) -> Vec<(VersionId, f64)> {
+        let mut changes: BTreeMap<VersionId, Vec<(&Bytes, Option<Rect>)>> = BTreeMap::new();
+        let (mut sides, mut count) = (0.0, 0);
+        for (name, history) in self.rects.iter() {
+            for (time, state) in history.0.iter() {
+                let geo = state.as_ref().map(|rect| rect.geo);
+                if let Some(geo) = geo {
+                    sides += geo.width().max(geo.height());
+                    count += 1;
+                }
+                changes.entry(*time).or_default().push((name, geo));
+            }
+        }
+        let cell = sides / count.max(1) as f64;
+        let mut grid = Grid::new(if cell.is_normal() { cell } else { 1.0 });
+
+        // The overlap area of every pair, under both of its names.
+        let mut pairs: BTreeMap<&Bytes, BTreeMap<&Bytes, f64>> = BTreeMap::new();
+        let mut res = vec![];
+        for v in 1..=self.version.0 {
+            for (name, geo) in changes.remove(&VersionId(v)).unwrap_or_default() {
+                for other in pairs.remove(name).unwrap_or_default().into_keys() {
+                    if let Some(others) = pairs.get_mut(other) {
+                        others.remove(name);
+                    }
+                }
+                grid.remove(name);
+                let Some(geo) = geo else {
+                    continue;
+                };
+                for (other, other_geo) in grid.overlapping(&geo) {
+                    let area = geo.intersection(&other_geo).unwrap().area();
+                    pairs.entry(name).or_default().insert(other, area);
+                    pairs.entry(other).or_default().insert(name, area);
+                }
+                grid.insert(name, geo);
+            }
+            let total = pairs
+                .iter()
+                .flat_map(|(a, others)| others.range::<&Bytes, _>((Excluded(a), Unbounded)))
+                .map(|(_, area)| area)
+                .sum();
+            res.push((VersionId(v), total));
+        }
+        res
+    }
+}
+
+#[test]
+fn overlap_report() {
+    use crate::{gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 2 2 1 1 1 ;\nADDRECT B 1 1 3 3 1 1 1 ;\nADDRECT C 2 0 4 1 1 1 1 ;\nADDRECT D 5 5 6 6 1 1 1 ;\nCOMMIT\nUPDRECT B 4 4 5 5 ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+
+    let report = db.slice(VersionId(1)).overlaps();
+    // `A` and `C` only touch, `B` and `C` do not meet.
+    assert_eq!(report.pairs.len(), 1);
+    assert_eq!(report.pairs[0].a, "A");
+    assert_eq!(report.pairs[0].b, "B");
+    assert_eq!(report.total_area, 1.0);
+
+    assert_eq!(
+        db.overlap_curve(),
+        vec![(VersionId(1), 1.0), (VersionId(2), 0.0)]
+    );
+}
+
+#[test]
+fn overlap_curve_matches_slices() {
+    use crate::{gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 2 2 ;\nADDRECT B 1 1 3 3 ;\nCOMMIT\nADDRECT C 0 0 4 4 ;\nCOMMIT\nUPDRECT A 5 5 6 6 ;\nDELRECT C ;\nCOMMIT\nADDRECT C 2 2 5.5 5.5 ;\nCOMMIT\nDELRECT B ;\nCOMMIT\nADDRECT DIE -1000 -1000 1000 1000 ;\nADDRECT E -1 -1 1 1 ;\nCOMMIT\nUPDRECT E 5 5 7 7 ;\nUPDRECT DIE 0 0 6 6 ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let sliced: Vec<_> = (1..=db.version.0)
+        .map(|v| (VersionId(v), db.slice(VersionId(v)).overlaps().total_area))
+        .collect();
+    assert_eq!(db.overlap_curve(), sliced);
+}
@@ -0,0 +1,127 @@
+use bytes::Bytes;
+use regex::Regex;
+
+use crate::{
+    db::r#impl::SlicedDb,
+    geo::{point::Point, rect::Rect},
+};
+
+/// Matches shape names against a glob or a regex. Both must match the whole name.
+#[derive(Clone, Debug)]
+pub struct Matcher(Regex);
+
+impl Matcher {
+    /// `*` matches any run of characters and `?` any single character, everything else is literal.
+    pub fn glob(pattern: &str) -> Result<Self, regex::Error> {
+        let mut re = String::from("^");
+        for c in pattern.chars() {
+            match c {
+                '*' => re.push_str(".*"),
+                '?' => re.push('.'),
+                c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        re.push('$');
+        Regex::new(&re).map(Self)
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(&format!("^(?:{})$", pattern)).map(Self)
+    }
+
+    pub fn is_match(&self, name: &[u8]) -> bool {
+        self.0.is_match(&String::from_utf8_lossy(name))
+    }
+}
+
+impl SlicedDb {
+    /// Names of the rects and lines matching `matcher`.
+    pub fn find(&self, matcher: &Matcher) -> Vec<Bytes> {
+        let rects = self.rects.keys();
+        let lines = self.lines.keys();
+        rects
+            .chain(lines)
+            .filter(|name| matcher.is_match(name))
+            .cloned()
+            .collect()
+    }
+
+    /// Names of the rects containing `p`, smallest first, then of the lines within `tolerance` of it.
+    pub fn hit(&self, p: &Point, tolerance: f64) -> Vec<Bytes> {
+        let mut rects: Vec<_> = self
+            .rects
+            .iter()
+            .filter(|(_, r)| r.geo.contains(p))
+            .collect();
+        rects.sort_by(|a, b| a.1.geo.area().total_cmp(&b.1.geo.area()));
+        let lines = self
+            .lines
+            .iter()
+            .filter(|(_, l)| l.geo.distance(p) <= tolerance);
+        rects
+            .into_iter()
+            .map(|(name, _)| name)
+            .chain(lines.map(|(name, _)| name))
+            .cloned()
+            .collect()
+    }
+
+    /// Names of the rects and lines lying entirely inside `area`.
+    pub fn within(&self, area: &Rect) -> Vec<Bytes> {
+        let rects = self
+            .rects
+            .iter()
+            .filter(|(_, r)| area.contains_rect(&r.geo));
+        let lines = self
+            .lines
+            .iter()
+            .filter(|(_, l)| area.contains_rect(&l.geo.bbox()));
+        rects
+            .map(|(name, _)| name)
+            .chain(lines.map(|(name, _)| name))
+            .cloned()
+            .collect()
+    }
+
+    /// The bounding box of the shapes named in `names`, `None` if none of them is alive.
+    pub fn bounds<'a>(&self, names: impl IntoIterator<Item = &'a Bytes>) -> Option<Rect> {
+        Rect::bounding(names.into_iter().filter_map(|name| {
+            let rect = self.rects.get(name).map(|r| r.geo);
+            rect.or_else(|| self.lines.get(name).map(|l| l.geo.bbox()))
+        }))
+    }
+}
+
+#[test]
+fn search() {
+    use crate::{db::version_controller::VersionId, gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT top/a1 0 0 4 4 ;\nADDRECT top/a2 1 1 2 2 ;\nADDRECT top/b1 5 5 6 6 ;\nADDLINE n1 0 5 4 5 ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let sliced = db.slice(VersionId(1));
+
+    let glob = Matcher::glob("top/a?").unwrap();
+    assert_eq!(sliced.find(&glob), vec!["top/a1", "top/a2"]);
+    let re = Matcher::regex(r"top/.1|n\d").unwrap();
+    assert_eq!(sliced.find(&re), vec!["top/a1", "top/b1", "n1"]);
+    assert!(Matcher::regex("(").is_err());
+    // Dots are literal in globs.
+    assert!(!Matcher::glob("top.a1").unwrap().is_match(b"top/a1"));
+
+    assert_eq!(
+        sliced.hit(&Point::new(1.5, 1.5), 0.1),
+        vec!["top/a2", "top/a1"]
+    );
+    assert_eq!(sliced.hit(&Point::new(2.0, 5.05), 0.1), vec!["n1"]);
+
+    let band = Rect::new(Point::new(0.5, 0.5), Point::new(6.5, 6.5));
+    assert_eq!(sliced.within(&band), vec!["top/a2", "top/b1"]);
+    assert_eq!(
+        sliced.bounds(&[Bytes::from("top/a2"), Bytes::from("n1")]),
+        Some(Rect::new(Point::new(0.0, 1.0), Point::new(4.0, 5.0)))
+    );
+}
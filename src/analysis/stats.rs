@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    db::{r#impl::Db, version_controller::VersionId},
+    geo::{point::Point, rect::Rect},
+};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VersionStats {
+    pub version: VersionId,
+    pub rects: usize,
+    pub lines: usize,
+    /// Sum of the areas of all the rects.
+    pub area: f64,
+    /// Bounding box of all the rects.
+    pub bbox: Option<Rect>,
+    /// Shapes added, modified and deleted by the commit of this version.
+    pub added: usize,
+    pub modified: usize,
+    pub deleted: usize,
+    /// Sum of the center shifts (Euclidean) of the rects modified by this commit.
+    pub displacement: f64,
+    /// Total area of the overlapping regions of the rects, as in `Db::overlap_curve`.
+    pub overlap: f64,
+}
+
+impl VersionStats {
+    /// Rect area over bounding box area.
+    pub fn utilization(&self) -> f64 {
+        match self.bbox {
+            Some(bbox) if bbox.area() > 0.0 => self.area / bbox.area(),
+            _ => 0.0,
+        }
+    }
+}
+
+/// An `f64` ordered by `total_cmp`, to be kept in ordered multisets.
+#[derive(Clone, Copy, PartialEq)]
+struct Key(f64);
+
+impl Eq for Key {}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Multisets of the rect sides, so that the bounding box follows insertions and removals.
+#[derive(Default)]
+struct Extent {
+    sides: [BTreeMap<Key, usize>; 4],
+}
+
+impl Extent {
+    fn values(r: &Rect) -> [f64; 4] {
+        [r.ll.x, r.ll.y, r.ur.x, r.ur.y]
+    }
+
+    fn insert(&mut self, r: &Rect) {
+        for (side, v) in self.sides.iter_mut().zip(Self::values(r)) {
+            *side.entry(Key(v)).or_default() += 1;
+        }
+    }
+
+    fn remove(&mut self, r: &Rect) {
+        for (side, v) in self.sides.iter_mut().zip(Self::values(r)) {
+            if let Some(cnt) = side.get_mut(&Key(v)) {
+                *cnt -= 1;
+                if *cnt == 0 {
+                    side.remove(&Key(v));
+                }
+            }
+        }
+    }
+
+    fn bbox(&self) -> Option<Rect> {
+        let [llx, lly, urx, ury] = &self.sides;
+        Some(Rect::new(
+            Point::new(llx.keys().next()?.0, lly.keys().next()?.0),
+            Point::new(urx.keys().next_back()?.0, ury.keys().next_back()?.0),
+        ))
+    }
+}
+
+/// A state change of one shape: the state before and after a commit.
+type Transition<T> = (Option<T>, Option<T>);
+
+impl Db {
+    /// Statistics of every version, indexed by version, genesis included.
+    /// Computed in one pass over the histories rather than by slicing each version.
+    pub fn stats(&self) -> Vec<VersionStats> {
+        let mut rect_events: BTreeMap<VersionId, Vec<Transition<Rect>>> = BTreeMap::new();
+        for history in self.rects.values() {
+            let mut prev = None;
+            for (version, state) in history.0.iter() {
+                let cur = state.as_ref().map(|r| r.geo);
+                rect_events.entry(*version).or_default().push((prev, cur));
+                prev = cur;
+            }
+        }
+        let mut line_events: BTreeMap<VersionId, Vec<(bool, bool)>> = BTreeMap::new();
+        for history in self.lines.values() {
+            let mut prev = false;
+            for (version, state) in history.0.iter() {
+                let cur = state.is_some();
+                line_events.entry(*version).or_default().push((prev, cur));
+                prev = cur;
+            }
+        }
+
+        let mut overlaps = self.overlap_curve().into_iter();
+        let mut res = Vec::with_capacity(self.version.0 as usize + 1);
+        let mut extent = Extent::default();
+        let mut cur = VersionStats::default();
+        for v in 0..=self.version.0 {
+            let version = VersionId(v);
+            cur = VersionStats {
+                version,
+                added: 0,
+                modified: 0,
+                deleted: 0,
+                displacement: 0.0,
+                ..cur
+            };
+            for (prev, next) in rect_events.remove(&version).unwrap_or_default() {
+                match (prev, next) {
+                    (None, Some(r)) => {
+                        cur.added += 1;
+                        cur.rects += 1;
+                        cur.area += r.area();
+                        extent.insert(&r);
+                    }
+                    (Some(p), Some(r)) => {
+                        cur.modified += 1;
+                        cur.area += r.area() - p.area();
+                        cur.displacement += p.center().euclidean(&r.center());
+                        extent.remove(&p);
+                        extent.insert(&r);
+                    }
+                    (Some(p), None) => {
+                        cur.deleted += 1;
+                        cur.rects -= 1;
+                        cur.area -= p.area();
+                        extent.remove(&p);
+                    }
+                    (None, None) => {}
+                }
+            }
+            for (prev, next) in line_events.remove(&version).unwrap_or_default() {
+                match (prev, next) {
+                    (false, true) => {
+                        cur.added += 1;
+                        cur.lines += 1;
+                    }
+                    (true, true) => cur.modified += 1,
+                    (true, false) => {
+                        cur.deleted += 1;
+                        cur.lines -= 1;
+                    }
+                    (false, false) => {}
+                }
+            }
+            cur.bbox = extent.bbox();
+            if v > 0 {
+                cur.overlap = overlaps.next().map_or(0.0, |(_, area)| area);
+            }
+            res.push(cur.clone());
+        }
+        res
+    }
+}
+
+#[test]
+fn version_stats() {
+    use crate::{gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 2 2 1 1 1 ;\nADDRECT B 2 2 4 4 1 1 1 ;\nADDLINE L 0 0 1 1 ;\nCOMMIT\nUPDRECT A 1 0 3 2 ;\nDELRECT B ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let stats = db.stats();
+    assert_eq!(stats.len(), 3);
+    assert_eq!(stats[0].rects, 0);
+    assert_eq!(stats[0].bbox, None);
+
+    assert_eq!((stats[1].rects, stats[1].lines), (2, 1));
+    assert_eq!(stats[1].added, 3);
+    assert_eq!(stats[1].area, 8.0);
+    assert_eq!(
+        stats[1].bbox,
+        Some(Rect::new(Point::new(0.0, 0.0), Point::new(4.0, 4.0)))
+    );
+    assert_eq!(stats[1].utilization(), 0.5);
+
+    assert_eq!((stats[2].modified, stats[2].deleted), (1, 1));
+    assert_eq!(stats[2].area, 4.0);
+    assert_eq!(stats[2].displacement, 1.0);
+    assert_eq!(
+        stats[2].bbox,
+        Some(Rect::new(Point::new(1.0, 0.0), Point::new(3.0, 2.0)))
+    );
+    assert_eq!(stats[2].utilization(), 1.0);
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 2 2 ;\nADDRECT B 1 1 3 3 ;\nCOMMIT\nDELRECT B ;\n".into(),
+        Config::default(),
+    )
+    .unwrap();
+    let overlaps: Vec<_> = db.stats().iter().map(|s| s.overlap).collect();
+    assert_eq!(overlaps, vec![0.0, 1.0, 0.0]);
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+
+use super::{
+    error::DbError,
+    r#impl::{Db, History},
+    version_controller::VersionId,
+};
+
+impl<T: Clone> History<T> {
+    /// Collapse every entry in `from..=to` into a single entry at `from`, holding the state at `to`.
+    /// Entries after `to` are shifted back so that versions stay contiguous.
+    pub fn squash(&mut self, from: VersionId, to: VersionId) {
+        let span = to.0 - from.0;
+        let later = self.0.split_off(&to.incr());
+        let merged = self.0.split_off(&from);
+        let alive_before = matches!(self.0.values().next_back(), Some(Some(_)));
+        if let Some((_, last)) = merged.into_iter().last() {
+            if last.is_some() || alive_before {
+                self.0.insert(from, last);
+            }
+        }
+        self.0.extend(
+            later
+                .into_iter()
+                .map(|(time, state)| (VersionId(time.0 - span), state)),
+        );
+    }
+
+    /// Drop intermediate entries whose version is not a multiple of `keep_every_n`.
+    /// The first and the last state of each lifetime of the shape, and every deletion, are always kept.
+    /// Returns the number of dropped entries.
+    pub fn gc(&mut self, keep_every_n: u32) -> usize {
+        let entries: Vec<_> = self.0.iter().map(|(t, s)| (*t, s.is_some())).collect();
+        let mut dropped = 0;
+        for (idx, (time, alive)) in entries.iter().enumerate() {
+            let is_first = idx == 0 || !entries[idx - 1].1;
+            let is_last = idx + 1 == entries.len() || !entries[idx + 1].1;
+            if !alive || is_first || is_last || time.0 % keep_every_n == 0 {
+                continue;
+            }
+            self.0.remove(time);
+            dropped += 1;
+        }
+        dropped
+    }
+}
+
+fn squash_all<T: Clone>(
+    histories: &mut BTreeMap<Bytes, History<T>>,
+    from: VersionId,
+    to: VersionId,
+) {
+    histories.retain(|_, history| {
+        history.squash(from, to);
+        !history.0.is_empty()
+    });
+}
+
+impl Db {
+    /// Merge versions `from..=to` into a single version `from`, dropping the intermediate histories.
+    /// Versions after `to` are renumbered, so `Db::version` shrinks by `to - from`.
+    pub fn squash(&mut self, from: VersionId, to: VersionId) -> Result<(), DbError> {
+        if to > self.version {
+            return Err(DbError::UnknownVersion { version: to });
+        }
+        if from == VersionId::GENESIS || from > to {
+            return Err(DbError::InvalidRange { from, to });
+        }
+        if from == to {
+            return Ok(());
+        }
+        squash_all(&mut self.rects, from, to);
+        squash_all(&mut self.lines, from, to);
+        let later = self.messages.split_off(&to.incr());
+        let merged: Vec<_> = self.messages.split_off(&from).into_values().collect();
+        if !merged.is_empty() {
+            self.messages.insert(from, merged.join("\n"));
+        }
+        let span = to.0 - from.0;
+        self.messages.extend(
+            later
+                .into_iter()
+                .map(|(time, message)| (VersionId(time.0 - span), message)),
+        );
+        self.version = VersionId(self.version.0 - (to.0 - from.0));
+        Ok(())
+    }
+
+    /// Thin out long traces, keeping only the states recorded on every `keep_every_n`-th version
+    /// together with the first and the last state of every shape.
+    /// Returns the number of dropped history entries.
+    pub fn gc(&mut self, keep_every_n: u32) -> Result<usize, DbError> {
+        if keep_every_n == 0 {
+            return Err(DbError::ZeroInterval);
+        }
+        let rects: usize = self.rects.values_mut().map(|h| h.gc(keep_every_n)).sum();
+        let lines: usize = self.lines.values_mut().map(|h| h.gc(keep_every_n)).sum();
+        Ok(rects + lines)
+    }
+}
+
+#[test]
+fn squash_squeeze() {
+    use crate::{gui::public::Config, public::parse};
+    use std::{io::Read, path::PathBuf, str::FromStr};
+
+    let prj_rt = project_root::get_project_root().unwrap();
+    let mut file =
+        std::fs::File::open(prj_rt.join(PathBuf::from_str("test/squeeze.txt").unwrap())).unwrap();
+    let mut raw_text = String::with_capacity(256);
+    file.read_to_string(&mut raw_text).unwrap();
+
+    let mut db = parse(raw_text, Config::default()).unwrap();
+    assert_eq!(db.version, VersionId(3));
+    assert_eq!(
+        db.squash(VersionId(2), VersionId(4)),
+        Err(DbError::UnknownVersion {
+            version: VersionId(4)
+        })
+    );
+    assert_eq!(
+        db.squash(VersionId(2), VersionId(1)),
+        Err(DbError::InvalidRange {
+            from: VersionId(2),
+            to: VersionId(1)
+        })
+    );
+    db.squash(VersionId(1), VersionId(2)).unwrap();
+    assert_eq!(db.version, VersionId(2));
+
+    let v1 = db.slice(VersionId(1));
+    assert_eq!(v1.rects.keys().collect::<Vec<_>>(), vec!["Y"]);
+    let v2 = db.slice(VersionId(2));
+    assert_eq!(v2.rects.keys().collect::<Vec<_>>(), vec!["X"]);
+    // `X` was added and removed inside the squashed range, only its re-add survives.
+    assert_eq!(db.rects[&Bytes::from("X")].0.len(), 1);
+}
+
+#[test]
+fn gc_keeps_endpoints() {
+    use crate::{gui::public::Config, public::parse};
+
+    let mut script = String::from("COMMIT\nADDRECT A 0 0 1 1 1 2 3 ;\n");
+    for i in 1..10 {
+        script.push_str(&format!("COMMIT\nUPDRECT A {} 0 {} 1 ;\n", i, i + 1));
+    }
+    script.push_str("COMMIT\nDELRECT A ;\n");
+    let mut db = parse(script, Config::default()).unwrap();
+    assert_eq!(db.rects[&Bytes::from("A")].0.len(), 11);
+
+    assert_eq!(db.gc(0), Err(DbError::ZeroInterval));
+    let dropped = db.gc(4).unwrap();
+    let kept: Vec<_> = db.rects[&Bytes::from("A")].0.keys().map(|v| v.0).collect();
+    assert_eq!(kept, vec![1, 4, 8, 10, 11]);
+    assert_eq!(dropped, 6);
+    assert_eq!(
+        db.slice(VersionId(10)).rects[&Bytes::from("A")].geo.ll.x,
+        9.0
+    );
+    assert!(db.slice(VersionId(11)).rects.is_empty());
+}
@@ -0,0 +1,135 @@
+use std::fmt::Display;
+
+use super::version_controller::VersionId;
+use crate::geo::validate::GeoIssue;
+
+/// What `Db::create_version` does when an action does not fit the current state of the db.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ConflictPolicy {
+    /// Reject the commit on the first bad action.
+    #[default]
+    Strict,
+    /// Skip bad actions and report them as warnings.
+    WarnAndSkip,
+    /// Turn an `Add` of an existing shape into an update, and a `Modify` of a missing shape into an add.
+    /// Anything that still does not fit is skipped with a warning.
+    Upsert,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum DbError {
+    /// `Add` of a shape that is still alive.
+    DuplicateAdd { version: VersionId, name: String },
+    /// `Modify` or `Delete` of a shape that does not exist, or has been deleted.
+    MissingShape { version: VersionId, name: String },
+    /// The geometry of the action is a line while its keyword names a rect, or the other way around.
+    WrongShapeKind {
+        version: VersionId,
+        name: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// `Add` without any geometry.
+    EmptyGeometry { version: VersionId, name: String },
+    /// The geometry is rejected, or has been normalized, by `Config::validation`.
+    InvalidGeometry {
+        version: VersionId,
+        name: String,
+        issue: GeoIssue,
+    },
+    /// A revert of `reverted` touching a shape which has changed again since.
+    ChangedSince {
+        version: VersionId,
+        name: String,
+        reverted: VersionId,
+    },
+    /// A version which is not in the db.
+    UnknownVersion { version: VersionId },
+    /// A range of versions which is empty, or starts at the genesis.
+    InvalidRange { from: VersionId, to: VersionId },
+    /// A garbage collection that keeps every 0-th version.
+    ZeroInterval,
+}
+
+impl DbError {
+    pub fn version(&self) -> Option<VersionId> {
+        match self {
+            DbError::DuplicateAdd { version, .. }
+            | DbError::MissingShape { version, .. }
+            | DbError::WrongShapeKind { version, .. }
+            | DbError::EmptyGeometry { version, .. }
+            | DbError::InvalidGeometry { version, .. }
+            | DbError::ChangedSince { version, .. }
+            | DbError::UnknownVersion { version } => Some(*version),
+            DbError::InvalidRange { from, .. } => Some(*from),
+            DbError::ZeroInterval => None,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            DbError::DuplicateAdd { name, .. }
+            | DbError::MissingShape { name, .. }
+            | DbError::WrongShapeKind { name, .. }
+            | DbError::EmptyGeometry { name, .. }
+            | DbError::InvalidGeometry { name, .. }
+            | DbError::ChangedSince { name, .. } => Some(name),
+            DbError::UnknownVersion { .. }
+            | DbError::InvalidRange { .. }
+            | DbError::ZeroInterval => None,
+        }
+    }
+}
+
+impl Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::DuplicateAdd { version, name } => {
+                write!(f, "commit {}: `{}` is added twice", version.0, name)
+            }
+            DbError::MissingShape { version, name } => {
+                write!(f, "commit {}: `{}` does not exist", version.0, name)
+            }
+            DbError::WrongShapeKind {
+                version,
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "commit {}: `{}` is a {}, found a {}",
+                version.0, name, expected, found
+            ),
+            DbError::EmptyGeometry { version, name } => {
+                write!(
+                    f,
+                    "commit {}: `{}` is added without geometry",
+                    version.0, name
+                )
+            }
+            DbError::InvalidGeometry {
+                version,
+                name,
+                issue,
+            } => write!(f, "commit {}: `{}`: {}", version.0, name, issue),
+            DbError::ChangedSince {
+                version,
+                name,
+                reverted,
+            } => write!(
+                f,
+                "commit {}: `{}` has changed since version {}",
+                version.0, name, reverted.0
+            ),
+            DbError::UnknownVersion { version } => {
+                write!(f, "version {} does not exist", version.0)
+            }
+            DbError::InvalidRange { from, to } => {
+                write!(f, "{}..={} is not a range of commits", from.0, to.0)
+            }
+            DbError::ZeroInterval => write!(f, "cannot keep every 0th version"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
@@ -0,0 +1,371 @@
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use bytes::Bytes;
+use serde_json::{json, Map, Value};
+
+use super::{
+    r#impl::{Db, LineInfo, RectInfo, Values},
+    version_controller::{ActionKind, VersionId},
+};
+use crate::{
+    geo::{color::Color, style::Style},
+    parser::r#impl::quote,
+};
+
+/// The state of a rect or a line, as written back to a script.
+#[derive(Clone, Copy)]
+enum Info<'a> {
+    Rect(&'a RectInfo),
+    Line(&'a LineInfo),
+}
+
+impl<'a> Info<'a> {
+    fn geometry(&self) -> [f64; 4] {
+        let (ll, ur) = match self {
+            Info::Rect(r) => (r.geo.ll, r.geo.ur),
+            Info::Line(l) => (l.geo.ll, l.geo.ur),
+        };
+        [ll.x, ll.y, ur.x, ur.y]
+    }
+
+    fn color(&self) -> Option<Color> {
+        match self {
+            Info::Rect(r) => r.color,
+            Info::Line(l) => l.color,
+        }
+    }
+
+    fn values(&self) -> &'a Values {
+        match self {
+            Info::Rect(r) => &r.values,
+            Info::Line(l) => &l.values,
+        }
+    }
+
+    fn desc(&self) -> Option<&'a Bytes> {
+        match self {
+            Info::Rect(r) => r.desc.as_ref(),
+            Info::Line(l) => l.desc.as_ref(),
+        }
+    }
+
+    fn style(&self) -> &'a Style {
+        match self {
+            Info::Rect(r) => &r.style,
+            Info::Line(l) => &l.style,
+        }
+    }
+}
+
+/// A change of one shape in one version.
+struct Entry<'a> {
+    action: ActionKind,
+    /// `RECT` or `LINE`, as in the keywords of the actions.
+    kind: &'static str,
+    name: &'a Bytes,
+    /// The state after the change, `None` for a deletion.
+    info: Option<Info<'a>>,
+}
+
+impl Db {
+    /// Every change of the db, by version, rects first.
+    fn entries(&self) -> BTreeMap<VersionId, Vec<Entry<'_>>> {
+        let mut res: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for (name, history) in self.rects.iter() {
+            for (version, entry) in history_entries(name, "RECT", &history.0, Info::Rect) {
+                res.entry(version).or_default().push(entry);
+            }
+        }
+        for (name, history) in self.lines.iter() {
+            for (version, entry) in history_entries(name, "LINE", &history.0, Info::Line) {
+                res.entry(version).or_default().push(entry);
+            }
+        }
+        res
+    }
+
+    /// `key=value` clauses of the values of a shape.
+    fn value_clauses(&self, values: &Values) -> String {
+        values
+            .0
+            .iter()
+            .map(|(channel, value)| format!(" {}={}", self.channels[*channel], value))
+            .collect()
+    }
+
+    fn action_line(&self, entry: &Entry) -> String {
+        let verb = match entry.action {
+            ActionKind::Add => "ADD",
+            ActionKind::Modify => "UPD",
+            ActionKind::Delete => "DEL",
+        };
+        let mut res = format!(
+            "{}{} {}",
+            verb,
+            entry.kind,
+            String::from_utf8_lossy(entry.name)
+        );
+        if let Some(info) = entry.info {
+            for x in info.geometry() {
+                write!(res, " {}", x).unwrap();
+            }
+            if let Some(c) = info.color() {
+                write!(res, " {}", c).unwrap();
+            }
+            res.push_str(&self.value_clauses(info.values()));
+            if let Some(d) = info.desc() {
+                write!(res, " desc={}", String::from_utf8_lossy(d)).unwrap();
+            }
+            let style = info.style();
+            if let Some(c) = style.stroke {
+                write!(res, " stroke={}", c).unwrap();
+            }
+            if let Some(w) = style.width {
+                write!(res, " width={}", w).unwrap();
+            }
+            if let Some(d) = style.dash {
+                write!(res, " dash={}", d).unwrap();
+            }
+            if style.fill.is_some() {
+                let fill = if style.is_outline() {
+                    "outline"
+                } else {
+                    "fill"
+                };
+                write!(res, " style={}", fill).unwrap();
+            }
+        }
+        res.push_str(" ;");
+        res
+    }
+
+    fn commit_line(&self, version: VersionId) -> String {
+        match self.message(version) {
+            Some(m) => format!("COMMIT {}", quote(m)),
+            None => "COMMIT".to_owned(),
+        }
+    }
+
+    /// The whole history as a script, one commit per version, each change spelled out in full.
+    /// Parsing it gives back the same db.
+    pub fn script(&self) -> String {
+        let mut entries = self.entries();
+        let mut res = String::new();
+        for version in 1..=self.version.0 {
+            let version = VersionId(version);
+            writeln!(res, "{}", self.commit_line(version)).unwrap();
+            for entry in entries.remove(&version).unwrap_or_default() {
+                writeln!(res, "{}", self.action_line(&entry)).unwrap();
+            }
+        }
+        res
+    }
+
+    /// A script of a single commit, adding every shape alive in `version`.
+    pub fn snapshot_script(&self, version: VersionId) -> String {
+        let sliced = self.slice(version);
+        let mut res = format!("{}\n", self.commit_line(version));
+        let rects = sliced.rects.iter().map(|(n, r)| (n, "RECT", Info::Rect(r)));
+        let lines = sliced.lines.iter().map(|(n, l)| (n, "LINE", Info::Line(l)));
+        for (name, kind, info) in rects.chain(lines) {
+            let entry = Entry {
+                action: ActionKind::Add,
+                kind,
+                name,
+                info: Some(info),
+            };
+            writeln!(res, "{}", self.action_line(&entry)).unwrap();
+        }
+        res
+    }
+
+    /// Every change of the shape `name`, as the versions making them and their actions.
+    pub fn log(&self, name: &[u8]) -> Vec<(VersionId, String)> {
+        let name = Bytes::copy_from_slice(name);
+        let rects = self.rects.get(&name).into_iter().flat_map(|h| {
+            let entries = history_entries(&name, "RECT", &h.0, Info::Rect);
+            entries.into_iter().map(|(v, e)| (v, self.action_line(&e)))
+        });
+        let lines = self.lines.get(&name).into_iter().flat_map(|h| {
+            let entries = history_entries(&name, "LINE", &h.0, Info::Line);
+            entries.into_iter().map(|(v, e)| (v, self.action_line(&e)))
+        });
+        let mut res: Vec<_> = rects.chain(lines).collect();
+        res.sort_by_key(|(v, _)| *v);
+        res
+    }
+
+    /// The shapes of `version` as CSV, with a column for every value channel.
+    pub fn snapshot_csv(&self, version: VersionId) -> String {
+        let sliced = self.slice(version);
+        let mut res = String::from("shape,name,llx,lly,urx,ury,color");
+        for channel in self.channels.iter() {
+            write!(res, ",{}", channel).unwrap();
+        }
+        res.push('\n');
+        let rects = sliced.rects.iter().map(|(n, r)| (n, "rect", Info::Rect(r)));
+        let lines = sliced.lines.iter().map(|(n, l)| (n, "line", Info::Line(l)));
+        for (name, kind, info) in rects.chain(lines) {
+            write!(res, "{},{}", kind, String::from_utf8_lossy(name)).unwrap();
+            for x in info.geometry() {
+                write!(res, ",{}", x).unwrap();
+            }
+            res.push(',');
+            if let Some(c) = info.color() {
+                write!(res, "{}", c).unwrap();
+            }
+            for channel in 0..self.channels.len() {
+                res.push(',');
+                if let Some(v) = info.values().get(channel) {
+                    write!(res, "{}", v).unwrap();
+                }
+            }
+            res.push('\n');
+        }
+        res
+    }
+
+    fn info_json(&self, name: &Bytes, info: Info) -> Value {
+        let values: Map<_, _> = info
+            .values()
+            .0
+            .iter()
+            .map(|(c, v)| (self.channels[*c].clone(), json!(v)))
+            .collect();
+        let style = info.style();
+        json!({
+            "name": String::from_utf8_lossy(name),
+            "geometry": info.geometry(),
+            "color": info.color().map(|c| c.to_string()),
+            "values": values,
+            "style": {
+                "stroke": style.stroke.map(|c| c.to_string()),
+                "width": style.width,
+                "dash": style.dash,
+                "outline": style.is_outline(),
+            },
+        })
+    }
+
+    /// The shapes of `version` as JSON.
+    pub fn snapshot_json(&self, version: VersionId) -> Value {
+        let sliced = self.slice(version);
+        let rects: Vec<_> = sliced
+            .rects
+            .iter()
+            .map(|(n, r)| self.info_json(n, Info::Rect(r)))
+            .collect();
+        let lines: Vec<_> = sliced
+            .lines
+            .iter()
+            .map(|(n, l)| self.info_json(n, Info::Line(l)))
+            .collect();
+        json!({
+            "version": version.0,
+            "message": self.message(version),
+            "rects": rects,
+            "lines": lines,
+        })
+    }
+
+    /// The whole history as JSON: the changes of every version.
+    pub fn json(&self) -> Value {
+        let mut entries = self.entries();
+        let versions: Vec<_> = (1..=self.version.0)
+            .map(VersionId)
+            .map(|version| {
+                let changes: Vec<_> = entries
+                    .remove(&version)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|e| {
+                        let mut change = match e.info {
+                            Some(info) => self.info_json(e.name, info),
+                            None => json!({ "name": String::from_utf8_lossy(e.name) }),
+                        };
+                        change["action"] = json!(format!("{:?}", e.action).to_lowercase());
+                        change["shape"] = json!(e.kind.to_lowercase());
+                        change
+                    })
+                    .collect();
+                json!({
+                    "version": version.0,
+                    "message": self.message(version),
+                    "changes": changes,
+                })
+            })
+            .collect();
+        json!({ "channels": self.channels, "versions": versions })
+    }
+}
+
+/// The changes recorded in the history of one shape, by version.
+fn history_entries<'a, T>(
+    name: &'a Bytes,
+    kind: &'static str,
+    history: &'a BTreeMap<VersionId, Option<T>>,
+    info: fn(&'a T) -> Info<'a>,
+) -> Vec<(VersionId, Entry<'a>)> {
+    let mut alive = false;
+    let mut res = vec![];
+    for (version, state) in history.iter() {
+        let action = match (alive, state) {
+            (false, Some(_)) => ActionKind::Add,
+            (true, Some(_)) => ActionKind::Modify,
+            (true, None) => ActionKind::Delete,
+            (false, None) => continue,
+        };
+        alive = state.is_some();
+        let entry = Entry {
+            action,
+            kind,
+            name,
+            info: state.as_ref().map(info),
+        };
+        res.push((*version, entry));
+    }
+    res
+}
+
+#[test]
+fn round_trip() {
+    use crate::{gui::public::Config, public::parse};
+
+    let script = "COMMIT \"place\"\nADDRECT A 0 0 1 1 #ff0000 slack=-0.5 ;\nADDLINE L 0 2 2 2 stroke=blue width=2 ;\n\
+                  COMMIT\nUPDRECT A 1 1 2 2 ;\nADDRECT B 3 3 4 4 desc=INVX1 style=outline ;\nCOMMIT \"drop \\\"A\\\"\\nnow\"\nDELRECT A ;\n";
+    let db = parse(script.into(), Config::default()).unwrap();
+    let again = parse(db.script(), Config::default()).unwrap();
+    assert_eq!(again.version, db.version);
+    assert_eq!(again.message(VersionId(3)), Some("drop \"A\"\nnow"));
+    assert_eq!(again.script(), db.script());
+    for v in 0..=db.version.0 {
+        let (a, b) = (db.slice(VersionId(v)), again.slice(VersionId(v)));
+        assert_eq!(a.rects, b.rects);
+        assert_eq!(a.lines, b.lines);
+    }
+    assert_eq!(
+        db.log(b"A"),
+        [
+            (
+                VersionId(1),
+                "ADDRECT A 0 0 1 1 #ff0000 slack=-0.5 ;".to_owned()
+            ),
+            (
+                VersionId(2),
+                "UPDRECT A 1 1 2 2 #ff0000 slack=-0.5 ;".to_owned()
+            ),
+            (VersionId(3), "DELRECT A ;".to_owned()),
+        ]
+    );
+
+    let snapshot = parse(db.snapshot_script(VersionId(2)), Config::default()).unwrap();
+    assert_eq!(snapshot.slice(VersionId(1)).rects.len(), 2);
+    assert_eq!(
+        db.snapshot_csv(VersionId(1)).lines().nth(1),
+        Some("rect,A,0,0,1,1,#ff0000,,-0.5")
+    );
+    let json = db.snapshot_json(VersionId(1));
+    assert_eq!(json["message"], "place");
+    assert_eq!(json["rects"][0]["values"]["slack"], -0.5);
+    assert_eq!(db.json()["versions"][2]["changes"][0]["action"], "delete");
+}
@@ -1,26 +1,67 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use bytes::Bytes;
 
-use super::version_controller::{Action, Commit, VersionId};
+use super::{
+    error::{ConflictPolicy, DbError},
+    version_controller::{Action, ActionKind, Commit, VersionId},
+};
 use crate::{
-    geo::{self, color::Color, criticality::Criticality, shape::Shape},
+    geo::{
+        color::{Attribute, Color},
+        shape::{Shape, ShapeKind},
+        style::Style,
+        validate::{GeoIssue, Validation},
+    },
     gui::public::Config,
 };
 
-#[derive(Clone, Debug)]
+/// Channel of a value given without a name, such as the trailing number of `ADDRECT`.
+pub const DEFAULT_CHANNEL: &str = "value";
+
+/// Raw scalar values of a shape, keyed by their index in `Db::channels`.
+/// They are kept as they are and only colored at view time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Values(pub Vec<(usize, f32)>);
+
+impl Values {
+    pub fn get(&self, channel: usize) -> Option<f32> {
+        self.0.iter().find(|(c, _)| *c == channel).map(|(_, v)| *v)
+    }
+
+    /// Set the value of `channel`, returns whether it has changed.
+    pub fn set(&mut self, channel: usize, value: f32) -> bool {
+        match self.0.iter_mut().find(|(c, _)| *c == channel) {
+            Some((_, v)) if *v == value => false,
+            Some((_, v)) => {
+                *v = value;
+                true
+            }
+            None => {
+                self.0.push((channel, value));
+                true
+            }
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub struct RectInfo {
     pub name: Bytes,
     pub geo: crate::geo::rect::Rect,
     pub color: Option<Color>,
+    pub values: Values,
+    pub style: Style,
     pub desc: Option<Bytes>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct LineInfo {
     pub name: Bytes,
     pub geo: crate::geo::line::Line,
     pub color: Option<Color>,
+    pub values: Values,
+    pub style: Style,
     pub desc: Option<Bytes>,
 }
 
@@ -52,12 +93,21 @@ impl<T: Clone> History<T> {
     pub fn query(&self, time: VersionId) -> Option<T> {
         self.0.range(..=time).last().and_then(|(_, r)| r.clone())
     }
+
+    /// The first version after `time` in which the shape is added, modified or deleted.
+    pub fn next_change(&self, time: VersionId) -> Option<VersionId> {
+        self.0.range(time.incr()..).next().map(|(t, _)| *t)
+    }
 }
 
 pub struct Db {
     pub version: VersionId,
     pub rects: BTreeMap<bytes::Bytes, History<RectInfo>>,
     pub lines: BTreeMap<bytes::Bytes, History<LineInfo>>,
+    /// Names of the value channels, `DEFAULT_CHANNEL` first.
+    pub channels: Vec<String>,
+    /// Commit messages, by version.
+    pub messages: BTreeMap<VersionId, String>,
     pub config: Config,
 }
 
@@ -67,16 +117,108 @@ pub struct SlicedDb {
     pub lines: BTreeMap<bytes::Bytes, LineInfo>,
 }
 
+impl SlicedDb {
+    /// Every value of `channel` in this version, sorted.
+    pub fn values(&self, channel: usize) -> Vec<f32> {
+        let rects = self.rects.values().filter_map(|r| r.values.get(channel));
+        let lines = self.lines.values().filter_map(|l| l.values.get(channel));
+        let mut res: Vec<_> = rects.chain(lines).collect();
+        res.sort_by(f32::total_cmp);
+        res
+    }
+}
+
 impl Db {
     pub fn new(cfg: Config) -> Self {
         Self {
             rects: BTreeMap::new(),
             lines: BTreeMap::new(),
             version: VersionId::GENESIS,
+            channels: vec![DEFAULT_CHANNEL.to_owned()],
+            messages: BTreeMap::new(),
             config: cfg,
         }
     }
 
+    /// Index of the channel named `name`, if any shape has ever had a value on it.
+    pub fn channel(&self, name: &str) -> Option<usize> {
+        self.channels.iter().position(|c| c == name)
+    }
+
+    fn channel_or_insert(&mut self, name: &str) -> usize {
+        self.channel(name).unwrap_or_else(|| {
+            self.channels.push(name.to_owned());
+            self.channels.len() - 1
+        })
+    }
+
+    /// Every value of `channel` in every version, sorted.
+    pub fn values(&self, channel: usize) -> Vec<f32> {
+        let rects = self
+            .rects
+            .values()
+            .flat_map(|h| h.0.values().flatten().filter_map(|r| r.values.get(channel)));
+        let lines = self
+            .lines
+            .values()
+            .flat_map(|h| h.0.values().flatten().filter_map(|l| l.values.get(channel)));
+        let mut res: Vec<_> = rects.chain(lines).collect();
+        res.sort_by(f32::total_cmp);
+        res
+    }
+
+    /// The category of a shape under `attribute`.
+    pub fn category(
+        &self,
+        attribute: &Attribute,
+        name: &[u8],
+        desc: Option<&Bytes>,
+        values: &Values,
+    ) -> Option<String> {
+        let value = match attribute {
+            Attribute::Channel(c) => self.channel(c).and_then(|c| values.get(c)),
+            _ => None,
+        };
+        attribute.of(name, desc.map(|d| d.as_ref()), value)
+    }
+
+    /// Every category of `attribute` in every version, sorted and deduplicated.
+    pub fn categories(&self, attribute: &Attribute) -> Vec<String> {
+        let rects = self.rects.iter().flat_map(|(name, h)| {
+            h.0.values()
+                .flatten()
+                .filter_map(move |r| self.category(attribute, name, r.desc.as_ref(), &r.values))
+        });
+        let lines = self.lines.iter().flat_map(|(name, h)| {
+            h.0.values()
+                .flatten()
+                .filter_map(move |l| self.category(attribute, name, l.desc.as_ref(), &l.values))
+        });
+        let res: BTreeSet<_> = rects.chain(lines).collect();
+        res.into_iter().collect()
+    }
+
+    /// The first version after `time` in which any of `names` changes.
+    pub fn next_change<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a Bytes>,
+        time: VersionId,
+    ) -> Option<VersionId> {
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let rect = self.rects.get(name).and_then(|h| h.next_change(time));
+                let line = self.lines.get(name).and_then(|h| h.next_change(time));
+                rect.into_iter().chain(line).min()
+            })
+            .min()
+    }
+
+    /// Message of the commit which created `version`, if it had one.
+    pub fn message(&self, version: VersionId) -> Option<&str> {
+        self.messages.get(&version).map(String::as_str)
+    }
+
     pub fn slice(&self, v: VersionId) -> SlicedDb {
         let rects = self
             .rects
@@ -96,163 +238,681 @@ impl Db {
         }
     }
 
-    pub fn create_version(&mut self, commit: Commit) {
+    /// Apply a commit as a new version.
+    /// The commit is staged action by action; if it is rejected, every staged write is rolled back
+    /// and the db is left exactly as it was before the call.
+    pub fn create_version(&mut self, commit: Commit) -> Result<Vec<DbError>, DbError> {
         self.version = self.version.incr();
-        for v in commit.rect_actions {
-            let action = v.action;
-            let name = Bytes::copy_from_slice(v.name.as_bytes());
-            match action {
-                super::version_controller::ActionKind::Add => {
-                    let color = match &self.config.color {
-                        crate::geo::color::ColorType::RGB => v.color,
-                        crate::geo::color::ColorType::Gradient {
-                            generator,
-                            min,
-                            max,
-                        } => v
-                            .gradient
-                            .map(|v| Criticality(v).color(*min, *max, generator)),
-                    };
-
-                    let desc = v.desc.map(|x| Bytes::copy_from_slice(x.as_bytes()));
-                    match v.geo {
-                        crate::geo::shape::Shape::Rect(rect) => {
-                            assert!(self
-                                .rects
-                                .get_mut(&name)
-                                .and_then(|history| history.query(self.version))
-                                .is_none());
-                            let rect = RectInfo {
-                                name: name.clone(),
-                                geo: rect,
-                                color,
-                                desc,
-                            };
-                            self.rects
-                                .entry(name.clone())
-                                .or_insert(History::new(self.version, rect.clone()))
-                                .update(self.version, rect.clone());
-                        }
-                        crate::geo::shape::Shape::Line(line) => {
-                            assert!(self
-                                .lines
-                                .get_mut(&name)
-                                .and_then(|history| history.query(self.version))
-                                .is_none());
-                            let line = LineInfo {
-                                name: name.clone(),
-                                geo: line,
-                                color,
-                                desc,
-                            };
-                            self.lines
-                                .entry(name.clone())
-                                .or_insert(History::new(self.version, line.clone()))
-                                .update(self.version, line.clone());
-                        }
-                        crate::geo::shape::Shape::None => {
-                            panic!("To update a geometric must have its shape.")
-                        }
+        let mut warnings = vec![];
+        let mut staged = vec![];
+        let channels = self.channels.len();
+        let message = commit.message;
+        for action in commit.rect_actions.into_iter().chain(commit.line_actions) {
+            let staging = (action.kind, Bytes::copy_from_slice(action.name.as_bytes()));
+            let known = self.channels.len();
+            match self.apply_action(action, &mut warnings) {
+                Ok(()) => staged.push(staging),
+                Err(e) => match self.config.policy {
+                    ConflictPolicy::Strict => {
+                        self.rollback(staged, channels);
+                        return Err(e);
+                    }
+                    // A skipped action leaves no channel behind either.
+                    ConflictPolicy::WarnAndSkip | ConflictPolicy::Upsert => {
+                        self.channels.truncate(known);
+                        warnings.push(e);
+                    }
+                },
+            }
+        }
+        if let Some(message) = message {
+            self.messages.insert(self.version, message);
+        }
+        Ok(warnings)
+    }
+
+    /// Drop the entries that the current version wrote for `staged` shapes and the channels it created
+    /// past the first `channels`, and step back a version.
+    fn rollback(&mut self, staged: Vec<(ShapeKind, Bytes)>, channels: usize) {
+        let version = self.version;
+        for (kind, name) in staged {
+            match kind {
+                ShapeKind::Rect => unstage(&mut self.rects, &name, version),
+                ShapeKind::Line => unstage(&mut self.lines, &name, version),
+            }
+        }
+        self.channels.truncate(channels);
+        self.version = VersionId(version.0 - 1);
+    }
+
+    /// Create a new version which undoes `version`:
+    /// every shape touched by `version` goes back to the state it had right before it.
+    /// A shape changed again since `version` conflicts with the revert, which `Config::policy` settles:
+    /// `Strict` rejects the revert, `WarnAndSkip` leaves the shape as it is, `Upsert` overwrites it.
+    /// The restored geometries go through `Config::validation` again.
+    /// A rejected revert leaves the db as it was. Returns the new version and the warnings.
+    pub fn revert(&mut self, version: VersionId) -> Result<(VersionId, Vec<DbError>), DbError> {
+        if version == VersionId::GENESIS || version > self.version {
+            return Err(DbError::UnknownVersion { version });
+        }
+        let now = self.version.incr();
+        let mut warnings = vec![];
+        let rects = revert_plan(&self.rects, &self.config, version, now, &mut warnings)?;
+        let lines = revert_plan(&self.lines, &self.config, version, now, &mut warnings)?;
+        self.version = now;
+        apply_plan(&mut self.rects, rects, now);
+        apply_plan(&mut self.lines, lines, now);
+        self.messages
+            .insert(now, format!("Revert version {}", version.0));
+        Ok((now, warnings))
+    }
+
+    /// Whether the `kind` shape named `name` is alive at the current version.
+    /// Rects and lines have names of their own, a rect and a line can share one.
+    fn is_alive(&self, kind: ShapeKind, name: &Bytes) -> bool {
+        let version = self.version;
+        match kind {
+            ShapeKind::Rect => self
+                .rects
+                .get(name)
+                .and_then(|h| h.query(version))
+                .is_some(),
+            ShapeKind::Line => self
+                .lines
+                .get(name)
+                .and_then(|h| h.query(version))
+                .is_some(),
+        }
+    }
+
+    fn apply_action(&mut self, action: Action, warnings: &mut Vec<DbError>) -> Result<(), DbError> {
+        let version = self.version;
+        let name = Bytes::copy_from_slice(action.name.as_bytes());
+        let upsert = self.config.policy == ConflictPolicy::Upsert;
+        if let Some(found) = action.geo.kind() {
+            if found != action.kind {
+                return Err(DbError::WrongShapeKind {
+                    version,
+                    name: action.name,
+                    expected: action.kind.name(),
+                    found: found.name(),
+                });
+            }
+        }
+        let alive = self.is_alive(action.kind, &name);
+
+        let kind = match (action.action, alive, upsert) {
+            (ActionKind::Add, false, _) | (ActionKind::Modify, false, true) => ActionKind::Add,
+            (ActionKind::Add, true, true) | (ActionKind::Modify, true, _) => ActionKind::Modify,
+            (ActionKind::Delete, true, _) => ActionKind::Delete,
+            (ActionKind::Add, true, false) => {
+                return Err(DbError::DuplicateAdd {
+                    version,
+                    name: action.name,
+                })
+            }
+            (ActionKind::Modify | ActionKind::Delete, false, _) => {
+                return Err(DbError::MissingShape {
+                    version,
+                    name: action.name,
+                })
+            }
+        };
+
+        let invalid = |issue| DbError::InvalidGeometry {
+            version,
+            name: action.name.clone(),
+            issue,
+        };
+        let (geo, issue) = action
+            .geo
+            .validate(&self.config.validation)
+            .map_err(invalid)?;
+        if let Some(issue) = issue {
+            warnings.push(invalid(issue));
+        }
+        let action = Action { geo, ..action };
+
+        let values = Values(
+            action
+                .values
+                .iter()
+                .map(|(name, value)| (self.channel_or_insert(name), *value))
+                .collect(),
+        );
+        let desc = action
+            .desc
+            .as_ref()
+            .map(|x| Bytes::copy_from_slice(x.as_bytes()));
+        match kind {
+            ActionKind::Add => {
+                let color = action.color;
+                match action.geo {
+                    Shape::Rect(geo) => {
+                        let rect = RectInfo {
+                            name: name.clone(),
+                            geo,
+                            color,
+                            values: values.clone(),
+                            style: action.style,
+                            desc,
+                        };
+                        self.rects
+                            .entry(name)
+                            .or_insert(History::new(version, rect.clone()))
+                            .update(version, rect);
+                    }
+                    Shape::Line(geo) => {
+                        let line = LineInfo {
+                            name: name.clone(),
+                            geo,
+                            color,
+                            values: values.clone(),
+                            style: action.style,
+                            desc,
+                        };
+                        self.lines
+                            .entry(name)
+                            .or_insert(History::new(version, line.clone()))
+                            .update(version, line);
+                    }
+                    Shape::None => {
+                        return Err(DbError::EmptyGeometry {
+                            version,
+                            name: action.name,
+                        })
                     }
                 }
-                super::version_controller::ActionKind::Modify => {
+            }
+            ActionKind::Modify => {
+                if action.kind == ShapeKind::Rect {
                     let histories = self.rects.get_mut(&name).unwrap();
-                    let mut rect = histories.query(self.version).unwrap();
+                    let mut rect = histories.query(version).unwrap();
                     let mut diff = false;
-                    let desc = v.desc.map(|x| Bytes::copy_from_slice(x.as_bytes()));
-                    if desc != rect.desc {
+                    // Only what the action mentions is updated, the rest carries over.
+                    if desc.is_some() && desc != rect.desc {
                         diff = true;
                         rect.desc = desc;
                     }
-                    if v.color != rect.color {
+                    if action.color.is_some() && action.color != rect.color {
                         diff = true;
-                        rect.color = v.color;
+                        rect.color = action.color;
                     }
-                    if let Shape::Rect(geo) = v.geo {
+                    for (channel, value) in values.0.iter() {
+                        diff |= rect.values.set(*channel, *value);
+                    }
+                    diff |= rect.style.merge(&action.style);
+                    if let Shape::Rect(geo) = action.geo {
                         if geo != rect.geo {
                             diff = true;
                             rect.geo = geo;
                         }
                     }
                     if diff {
-                        histories.update(self.version, rect);
+                        histories.update(version, rect);
+                    }
+                } else {
+                    let histories = self.lines.get_mut(&name).unwrap();
+                    let mut line = histories.query(version).unwrap();
+                    let mut diff = false;
+                    // Only what the action mentions is updated, the rest carries over.
+                    if desc.is_some() && desc != line.desc {
+                        diff = true;
+                        line.desc = desc;
+                    }
+                    if action.color.is_some() && action.color != line.color {
+                        diff = true;
+                        line.color = action.color;
+                    }
+                    for (channel, value) in values.0.iter() {
+                        diff |= line.values.set(*channel, *value);
+                    }
+                    diff |= line.style.merge(&action.style);
+                    if let Shape::Line(geo) = action.geo {
+                        if geo != line.geo {
+                            diff = true;
+                            line.geo = geo;
+                        }
+                    }
+                    if diff {
+                        histories.update(version, line);
                     }
                 }
-                super::version_controller::ActionKind::Delete => {
-                    assert!(self.rects.contains_key(&name));
-                    let histories = self.rects.get_mut(&name).unwrap();
-                    histories.del(self.version);
+            }
+            ActionKind::Delete => {
+                if action.kind == ShapeKind::Rect {
+                    self.rects.get_mut(&name).unwrap().del(version);
+                } else {
+                    self.lines.get_mut(&name).unwrap().del(version);
                 }
             }
         }
-        handle_line_action(self, commit.line_actions);
+        Ok(())
     }
 }
 
-pub fn handle_line_action(db: &mut Db, line_actions: Vec<Action>) {
-    let mut handle_a_single_action = |action: Action| {
-        let color = match &db.config.color {
-            crate::geo::color::ColorType::RGB => action.color,
-            crate::geo::color::ColorType::Gradient {
-                generator,
-                min,
-                max,
-            } => action
-                .gradient
-                .map(|v| Criticality(v).color(*min, *max, generator)),
-        };
+fn unstage<T: Clone>(
+    histories: &mut BTreeMap<Bytes, History<T>>,
+    name: &Bytes,
+    version: VersionId,
+) {
+    if let Some(history) = histories.get_mut(name) {
+        history.0.remove(&version);
+        if history.0.is_empty() {
+            histories.remove(name);
+        }
+    }
+}
 
-        let desc = action
-            .desc
-            .as_ref()
-            .map(|x| Bytes::copy_from_slice(x.as_bytes()));
-        let name = Bytes::copy_from_slice(action.name.as_bytes());
-        let geo::shape::Shape::Line(raw) = &action.geo else {
-            panic!();
-        };
-        let line = LineInfo {
-            name: name.clone(),
-            geo: *raw,
-            color,
-            desc: desc.clone(),
-        };
-        match action.action {
-            super::version_controller::ActionKind::Add => {
-                db.lines
-                    .entry(name.clone())
-                    .or_insert(History::new(db.version, line.clone()))
-                    .update(db.version, line);
+/// A shape state whose geometry can be checked again.
+trait Validated: Sized {
+    fn validated(self, cfg: &Validation) -> Result<(Self, Option<GeoIssue>), GeoIssue>;
+}
+
+impl Validated for RectInfo {
+    fn validated(self, cfg: &Validation) -> Result<(Self, Option<GeoIssue>), GeoIssue> {
+        let (geo, issue) = self.geo.validate(cfg)?;
+        Ok((RectInfo { geo, ..self }, issue))
+    }
+}
+
+impl Validated for LineInfo {
+    fn validated(self, cfg: &Validation) -> Result<(Self, Option<GeoIssue>), GeoIssue> {
+        let (geo, issue) = self.geo.validate(cfg)?;
+        Ok((LineInfo { geo, ..self }, issue))
+    }
+}
+
+/// The states that revert `version` in a new version `now`, `None` for a deletion.
+fn revert_plan<T: Clone + PartialEq + Validated>(
+    histories: &BTreeMap<Bytes, History<T>>,
+    cfg: &Config,
+    version: VersionId,
+    now: VersionId,
+    warnings: &mut Vec<DbError>,
+) -> Result<Vec<(Bytes, Option<T>)>, DbError> {
+    let before = VersionId(version.0 - 1);
+    let upsert = cfg.policy == ConflictPolicy::Upsert;
+    let mut plan = vec![];
+    for (name, history) in histories.iter() {
+        if !history.0.contains_key(&version) {
+            continue;
+        }
+        let target = history.query(before);
+        let written = history.query(version);
+        let current = history.query(VersionId(now.0 - 1));
+        let text = String::from_utf8_lossy(name).into_owned();
+        let conflict = match (&target, &written, &current) {
+            // Added by `version`, deleted since.
+            (None, _, None) => Some(DbError::MissingShape {
+                version: now,
+                name: text.clone(),
+            }),
+            // Deleted by `version`, added again since.
+            (Some(_), None, Some(_)) if !upsert => Some(DbError::DuplicateAdd {
+                version: now,
+                name: text.clone(),
+            }),
+            // Modified by `version`, deleted since.
+            (Some(_), Some(_), None) if !upsert => Some(DbError::MissingShape {
+                version: now,
+                name: text.clone(),
+            }),
+            // Written by `version`, modified since.
+            (_, Some(written), Some(current)) if written != current && !upsert => {
+                Some(DbError::ChangedSince {
+                    version: now,
+                    name: text.clone(),
+                    reverted: version,
+                })
             }
-            super::version_controller::ActionKind::Modify => {
-                let histories = db.lines.get_mut(&name).unwrap();
-                let mut line_info = histories.query(db.version).unwrap();
-                let mut diff = false;
-                if desc != line_info.desc {
-                    diff = true;
-                    line_info.desc = desc;
-                }
-                if action.color != line_info.color {
-                    diff = true;
-                    line_info.color = action.color;
-                }
-                if let Shape::Line(geo) = action.geo {
-                    if geo != line_info.geo {
-                        diff = true;
-                        line_info.geo = geo;
+            _ => None,
+        };
+        let state = match target {
+            Some(state) => match state.validated(&cfg.validation) {
+                Ok((state, issue)) => {
+                    if let Some(issue) = issue {
+                        warnings.push(DbError::InvalidGeometry {
+                            version: now,
+                            name: text.clone(),
+                            issue,
+                        });
                     }
+                    Ok(Some(state))
                 }
-                if diff {
-                    histories.update(db.version, line_info);
-                }
-            }
-            super::version_controller::ActionKind::Delete => {
-                assert!(db.lines.contains_key(&name));
-                let histories = db.lines.get_mut(&name).unwrap();
-                histories.del(db.version);
+                Err(issue) => Err(DbError::InvalidGeometry {
+                    version: now,
+                    name: text,
+                    issue,
+                }),
+            },
+            None => Ok(None),
+        };
+        match (conflict.map_or(state, Err), cfg.policy) {
+            (Ok(state), _) => plan.push((name.clone(), state)),
+            (Err(e), ConflictPolicy::Strict) => return Err(e),
+            (Err(e), ConflictPolicy::WarnAndSkip | ConflictPolicy::Upsert) => warnings.push(e),
+        }
+    }
+    Ok(plan)
+}
+
+fn apply_plan<T: Clone>(
+    histories: &mut BTreeMap<Bytes, History<T>>,
+    plan: Vec<(Bytes, Option<T>)>,
+    now: VersionId,
+) {
+    for (name, state) in plan {
+        let history = histories.get_mut(&name).unwrap();
+        match state {
+            Some(state) => {
+                history.update(now, state);
             }
+            None => history.del(now),
         }
-    };
-    for action in line_actions {
-        handle_a_single_action(action);
     }
 }
+
+#[test]
+fn conflict_policy() {
+    use crate::public::{parse_with_warnings, ScriptError};
+
+    let script =
+        "COMMIT\nADDRECT A 0 0 1 1 1 2 3 ;\nCOMMIT\nADDRECT A 1 1 2 2 1 2 3 ;\nDELRECT B ;\n";
+    let cfg = |policy| Config {
+        policy,
+        ..Default::default()
+    };
+
+    let err = parse_with_warnings(script.into(), cfg(ConflictPolicy::Strict)).err();
+    assert_eq!(
+        err,
+        Some(ScriptError::Db(DbError::DuplicateAdd {
+            version: VersionId(2),
+            name: "A".into()
+        }))
+    );
+
+    let (db, warnings) =
+        parse_with_warnings(script.into(), cfg(ConflictPolicy::WarnAndSkip)).unwrap();
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(
+        db.slice(VersionId(2)).rects[&Bytes::from("A")].geo.ll.x,
+        0.0
+    );
+
+    let (db, warnings) = parse_with_warnings(script.into(), cfg(ConflictPolicy::Upsert)).unwrap();
+    assert_eq!(
+        warnings,
+        vec![DbError::MissingShape {
+            version: VersionId(2),
+            name: "B".into()
+        }]
+    );
+    assert_eq!(
+        db.slice(VersionId(2)).rects[&Bytes::from("A")].geo.ll.x,
+        1.0
+    );
+}
+
+#[test]
+fn atomic_commit_and_revert() {
+    use crate::public::parse;
+
+    let mut db = parse(
+        "COMMIT\nADDRECT A 0 0 1 1 1 2 3 ;\nCOMMIT\nUPDRECT A 1 1 2 2 ;\nADDRECT B 0 0 1 1 1 2 3 ;\n".into(),
+        Config::default(),
+    )
+    .unwrap();
+
+    let bad = Commit::build(
+        vec![],
+        vec![
+            Action {
+                action: ActionKind::Delete,
+                kind: ShapeKind::Rect,
+                name: "A".into(),
+                geo: Shape::None,
+                desc: None,
+                color: None,
+                values: vec![],
+                style: Style::default(),
+            },
+            Action {
+                action: ActionKind::Modify,
+                kind: ShapeKind::Rect,
+                name: "C".into(),
+                geo: Shape::None,
+                desc: None,
+                color: None,
+                values: vec![],
+                style: Style::default(),
+            },
+        ],
+    );
+    assert!(db.create_version(bad).is_err());
+    assert_eq!(db.version, VersionId(2));
+    assert_eq!(db.rects[&Bytes::from("A")].0.len(), 2);
+
+    assert_eq!(db.revert(VersionId(2)), Ok((VersionId(3), vec![])));
+    let v3 = db.slice(VersionId(3));
+    assert_eq!(v3.rects.keys().collect::<Vec<_>>(), vec!["A"]);
+    assert_eq!(v3.rects[&Bytes::from("A")].geo.ll.x, 0.0);
+    assert_eq!(
+        db.revert(VersionId(9)),
+        Err(DbError::UnknownVersion {
+            version: VersionId(9)
+        })
+    );
+}
+
+#[test]
+fn revert_conflicts() {
+    use crate::{lexer::Cursor, parser::r#impl::StringParser};
+
+    let cfg = |policy| Config {
+        policy,
+        ..Config::default()
+    };
+    let script = "COMMIT\nADDRECT A 0 0 1 1 ;\nCOMMIT\nDELRECT A ;\nCOMMIT\nADDRECT A 2 2 3 3 ;\nCOMMIT\nADDRECT B 0 0 1 1 heat=1 ;\nUPDRECT C 1 ;\n";
+    let load = |policy| {
+        let bytes = Bytes::from(script);
+        let mut db = Db::new(cfg(policy));
+        let commits = StringParser::new(&bytes, Cursor::new(script))
+            .parse()
+            .unwrap();
+        let results: Vec<_> = commits.into_iter().map(|c| db.create_version(c)).collect();
+        (db, results)
+    };
+
+    // The channel created by the rejected commit goes with it.
+    let (mut db, results) = load(ConflictPolicy::Strict);
+    assert!(results[3].is_err());
+    assert_eq!(db.channel("heat"), None);
+    assert_eq!(
+        db.revert(VersionId(2)),
+        Err(DbError::DuplicateAdd {
+            version: VersionId(4),
+            name: "A".into()
+        })
+    );
+    assert_eq!(db.version, VersionId(3));
+
+    let (mut db, _) = load(ConflictPolicy::WarnAndSkip);
+    let (now, warnings) = db.revert(VersionId(2)).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(db.slice(now).rects[&Bytes::from("A")].geo.ll.x, 2.0);
+
+    let (mut db, _) = load(ConflictPolicy::Upsert);
+    let (now, warnings) = db.revert(VersionId(2)).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(db.slice(now).rects[&Bytes::from("A")].geo.ll.x, 0.0);
+
+    // Modified by the reverted version, or added by it, then modified since.
+    let script =
+        "COMMIT\nADDRECT M 0 0 1 1 ;\nCOMMIT\nUPDRECT M 0 0 2 2 ;\nCOMMIT\nUPDRECT M 0 0 3 3 ;\n";
+    let load = |policy| crate::public::parse(script.into(), cfg(policy)).unwrap();
+    let changed = |reverted| DbError::ChangedSince {
+        version: VersionId(4),
+        name: "M".into(),
+        reverted,
+    };
+    let mut db = load(ConflictPolicy::Strict);
+    assert_eq!(db.revert(VersionId(2)), Err(changed(VersionId(2))));
+    assert_eq!(db.revert(VersionId(1)), Err(changed(VersionId(1))));
+    assert_eq!(db.version, VersionId(3));
+
+    let mut db = load(ConflictPolicy::WarnAndSkip);
+    let (now, warnings) = db.revert(VersionId(2)).unwrap();
+    assert_eq!(warnings, vec![changed(VersionId(2))]);
+    assert_eq!(db.slice(now).rects[&Bytes::from("M")].geo.ur.x, 3.0);
+
+    let mut db = load(ConflictPolicy::Upsert);
+    let (now, warnings) = db.revert(VersionId(2)).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(db.slice(now).rects[&Bytes::from("M")].geo.ur.x, 1.0);
+}
+
+#[test]
+fn names_per_kind() {
+    use crate::public::parse;
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 1 1 ;\nADDLINE A 0 0 1 1 ;\nCOMMIT\nDELLINE A ;\nUPDRECT A 0 0 2 2 ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let v1 = db.slice(VersionId(1));
+    assert_eq!((v1.rects.len(), v1.lines.len()), (1, 1));
+    let v2 = db.slice(VersionId(2));
+    assert_eq!(v2.rects[&Bytes::from("A")].geo.ur.x, 2.0);
+    assert!(v2.lines.is_empty());
+
+    let mut db = Db::new(Config::default());
+    let err = db.create_version(Commit::build(
+        vec![],
+        vec![Action {
+            action: ActionKind::Add,
+            kind: ShapeKind::Rect,
+            name: "A".into(),
+            geo: Shape::Line(crate::geo::line::Line {
+                ll: crate::geo::point::Point { x: 0.0, y: 0.0 },
+                ur: crate::geo::point::Point { x: 1.0, y: 1.0 },
+            }),
+            desc: None,
+            color: None,
+            values: vec![],
+            style: Style::default(),
+        }],
+    ));
+    assert_eq!(
+        err,
+        Err(DbError::WrongShapeKind {
+            version: VersionId(1),
+            name: "A".into(),
+            expected: "rect",
+            found: "line"
+        })
+    );
+}
+
+#[test]
+fn geometry_warnings() {
+    use crate::{
+        geo::validate::GeoIssue,
+        public::{parse_with_warnings, ScriptError},
+    };
+
+    let script = "COMMIT\nADDRECT A 1 1 0 0 1 2 3 ;\nADDRECT B 0 0 0 1 1 2 3 ;\n";
+    // Empty geometries are accepted unless asked otherwise.
+    let (db, _) = parse_with_warnings(script.into(), Config::default()).unwrap();
+    assert_eq!(db.slice(VersionId(1)).rects.len(), 2);
+
+    let validation = Validation {
+        allow_empty: false,
+        ..Default::default()
+    };
+    let strict = Config {
+        validation,
+        ..Default::default()
+    };
+    let err = parse_with_warnings(script.into(), strict).err();
+    assert_eq!(
+        err,
+        Some(ScriptError::Db(DbError::InvalidGeometry {
+            version: VersionId(1),
+            name: "B".into(),
+            issue: GeoIssue::Empty
+        }))
+    );
+
+    let cfg = Config {
+        policy: ConflictPolicy::WarnAndSkip,
+        validation,
+        ..Default::default()
+    };
+    let (db, warnings) = parse_with_warnings(script.into(), cfg).unwrap();
+    assert_eq!(warnings.len(), 2);
+    assert_eq!(
+        warnings[0],
+        DbError::InvalidGeometry {
+            version: VersionId(1),
+            name: "A".into(),
+            issue: GeoIssue::Inverted
+        }
+    );
+    let v1 = db.slice(VersionId(1));
+    assert_eq!(v1.rects.keys().collect::<Vec<_>>(), vec!["A"]);
+    assert_eq!(v1.rects[&Bytes::from("A")].geo.ur.x, 1.0);
+}
+
+#[test]
+fn value_channels() {
+    use crate::public::parse;
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 1 1 5 slack=-2.5 ;\nCOMMIT\nUPDRECT A slack=3 ;\n".into(),
+        Config::default(),
+    )
+    .unwrap();
+    assert_eq!(db.channels, vec!["value", "slack"]);
+    let slack = db.channel("slack").unwrap();
+    let a = &db.slice(VersionId(2)).rects[&Bytes::from("A")];
+    assert_eq!(a.values.get(0), Some(5.0));
+    assert_eq!(a.values.get(slack), Some(3.0));
+    assert_eq!(db.values(slack), vec![-2.5, 3.0]);
+
+    // Upserting a missing shape without a geometry is skipped, along with its channel.
+    let cfg = Config {
+        policy: ConflictPolicy::Upsert,
+        ..Default::default()
+    };
+    let (db, warnings) = crate::public::parse_with_warnings(
+        "COMMIT\nUPDRECT X foo=1 ;\nADDRECT A 0 0 1 1 bar=2 ;\n".into(),
+        cfg,
+    )
+    .unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(db.channels, vec!["value", "bar"]);
+}
+
+#[test]
+fn next_change() {
+    use crate::public::parse;
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 1 1 ;\nADDLINE L 0 0 1 1 ;\nCOMMIT\nUPDRECT A 0 0 2 2 ;\nCOMMIT\nCOMMIT\nDELLINE L ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let (a, l) = (Bytes::from("A"), Bytes::from("L"));
+    assert_eq!(db.next_change([&a], VersionId(1)), Some(VersionId(2)));
+    assert_eq!(db.next_change([&a], VersionId(2)), None);
+    assert_eq!(db.next_change([&l], VersionId(1)), Some(VersionId(4)));
+    assert_eq!(
+        db.next_change([&a, &l], VersionId::GENESIS),
+        Some(VersionId(1))
+    );
+}
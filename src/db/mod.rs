@@ -1,2 +1,5 @@
+pub mod compact;
+pub mod error;
+pub mod export;
 pub mod r#impl;
 pub mod version_controller;
@@ -1,8 +1,14 @@
-use crate::geo::{color::Color, shape::Shape};
+use crate::geo::{
+    color::Color,
+    shape::{Shape, ShapeKind},
+    style::Style,
+};
 
 pub struct Commit {
     pub rect_actions: Vec<Action>,
     pub line_actions: Vec<Action>,
+    /// The quoted string following `COMMIT`.
+    pub message: Option<String>,
 }
 
 impl Default for Commit {
@@ -16,6 +22,7 @@ impl Commit {
         Self {
             line_actions: vec![],
             rect_actions: vec![],
+            message: None,
         }
     }
 
@@ -26,19 +33,25 @@ impl Commit {
         Self {
             line_actions,
             rect_actions,
+            message: None,
         }
     }
 }
 
 pub struct Action {
     pub action: ActionKind,
+    /// The kind named by the keyword, `DELLINE` is a line action even without a geometry.
+    pub kind: ShapeKind,
     pub name: String,
     pub geo: Shape,
     pub desc: Option<String>,
     pub color: Option<Color>,
-    pub gradient: Option<f32>,
+    /// Named scalar values, see `db::r#impl::Values`.
+    pub values: Vec<(String, f32)>,
+    pub style: Style,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ActionKind {
     Add,
     Modify,
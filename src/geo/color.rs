@@ -1,7 +1,32 @@
-use std::u8;
+use std::collections::BTreeMap;
 
 use colorgrad::{self, Gradient};
 
+use super::colormap::ColorMap;
+
+/// How the range of values spread over a gradient is found.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum ValueRange {
+    /// The `min` and `max` given with the gradient.
+    #[default]
+    Fixed,
+    /// Smallest and largest value over all versions.
+    Global,
+    /// Smallest and largest value in the version on display.
+    PerVersion,
+    /// The `low` and `high` quantiles, in `0..=1`, over all versions, so that outliers do not
+    /// squeeze everything else into one color.
+    Percentile { low: f32, high: f32 },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Scale {
+    #[default]
+    Linear,
+    /// Symmetric log, `sign(v) * ln(1 + |v|)`, which also handles zero and negative values.
+    Log,
+}
+
 #[derive(Default)]
 pub enum ColorType {
     #[default]
@@ -10,8 +35,258 @@ pub enum ColorType {
         generator: Box<dyn colorgrad::Gradient>,
         min: f32,
         max: f32,
+        range: ValueRange,
+        scale: Scale,
+        /// Center the range on zero, for signed values such as slack.
+        diverging: bool,
+        /// Name of the value channel to color by.
+        channel: String,
+    },
+    /// A distinct color from `palette` for each value of `attribute`.
+    Categorical {
+        palette: &'static Palette,
+        attribute: Attribute,
+    },
+}
+
+/// What shapes are grouped by in categorical coloring.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Attribute {
+    Name,
+    /// The first `depth` components of the `/`-separated hierarchical name.
+    Prefix {
+        depth: usize,
+    },
+    /// The description, such as a cell master or a net.
+    Desc,
+    /// The value on a channel, such as a cluster id.
+    Channel(String),
+}
+
+impl Attribute {
+    /// The category of a shape, `None` if it lacks the attribute.
+    /// `value` is the value of the shape on the channel of `Attribute::Channel`.
+    pub fn of(&self, name: &[u8], desc: Option<&[u8]>, value: Option<f32>) -> Option<String> {
+        match self {
+            Attribute::Name => Some(String::from_utf8_lossy(name).into_owned()),
+            Attribute::Prefix { depth } => {
+                let name = String::from_utf8_lossy(name);
+                Some(name.split('/').take(*depth).collect::<Vec<_>>().join("/"))
+            }
+            Attribute::Desc => desc.map(|d| String::from_utf8_lossy(d).into_owned()),
+            Attribute::Channel(_) => value.map(|v| v.to_string()),
+        }
+    }
+}
+
+/// A named list of distinct colors.
+#[derive(PartialEq, Debug)]
+pub struct Palette {
+    pub name: &'static str,
+    pub colors: &'static [Color],
+}
+
+/// The palettes known to `palette`.
+pub static PALETTES: [Palette; 4] = [
+    Palette {
+        name: "tableau10",
+        colors: &[
+            Color::rgb(0x4e, 0x79, 0xa7),
+            Color::rgb(0xf2, 0x8e, 0x2b),
+            Color::rgb(0xe1, 0x57, 0x59),
+            Color::rgb(0x76, 0xb7, 0xb2),
+            Color::rgb(0x59, 0xa1, 0x4f),
+            Color::rgb(0xed, 0xc9, 0x48),
+            Color::rgb(0xb0, 0x7a, 0xa1),
+            Color::rgb(0xff, 0x9d, 0xa7),
+            Color::rgb(0x9c, 0x75, 0x5f),
+            Color::rgb(0xba, 0xb0, 0xac),
+        ],
+    },
+    Palette {
+        name: "set1",
+        colors: &[
+            Color::rgb(0xe4, 0x1a, 0x1c),
+            Color::rgb(0x37, 0x7e, 0xb8),
+            Color::rgb(0x4d, 0xaf, 0x4a),
+            Color::rgb(0x98, 0x4e, 0xa3),
+            Color::rgb(0xff, 0x7f, 0x00),
+            Color::rgb(0xff, 0xff, 0x33),
+            Color::rgb(0xa6, 0x56, 0x28),
+            Color::rgb(0xf7, 0x81, 0xbf),
+            Color::rgb(0x99, 0x99, 0x99),
+        ],
+    },
+    Palette {
+        name: "dark2",
+        colors: &[
+            Color::rgb(0x1b, 0x9e, 0x77),
+            Color::rgb(0xd9, 0x5f, 0x02),
+            Color::rgb(0x75, 0x70, 0xb3),
+            Color::rgb(0xe7, 0x29, 0x8a),
+            Color::rgb(0x66, 0xa6, 0x1e),
+            Color::rgb(0xe6, 0xab, 0x02),
+            Color::rgb(0xa6, 0x76, 0x1d),
+            Color::rgb(0x66, 0x66, 0x66),
+        ],
+    },
+    Palette {
+        name: "paired",
+        colors: &[
+            Color::rgb(0xa6, 0xce, 0xe3),
+            Color::rgb(0x1f, 0x78, 0xb4),
+            Color::rgb(0xb2, 0xdf, 0x8a),
+            Color::rgb(0x33, 0xa0, 0x2c),
+            Color::rgb(0xfb, 0x9a, 0x99),
+            Color::rgb(0xe3, 0x1a, 0x1c),
+            Color::rgb(0xfd, 0xbf, 0x6f),
+            Color::rgb(0xff, 0x7f, 0x00),
+            Color::rgb(0xca, 0xb2, 0xd6),
+            Color::rgb(0x6a, 0x3d, 0x9a),
+            Color::rgb(0xff, 0xff, 0x99),
+            Color::rgb(0xb1, 0x59, 0x28),
+        ],
     },
+];
+
+/// The palette named `name`.
+pub fn palette(name: &str) -> Option<&'static Palette> {
+    PALETTES.iter().find(|p| p.name == name)
+}
+
+/// Colors of the categories of a db. A category keeps its color in every version,
+/// as colors are handed out in the order of all categories ever seen, wrapping around the palette.
+pub struct CategoryMap<'a> {
+    pub palette: &'a Palette,
+    pub index: BTreeMap<String, usize>,
+}
+
+impl CategoryMap<'_> {
+    pub fn color(&self, category: &str) -> Option<Color> {
+        let idx = self.index.get(category)?;
+        Some(self.palette.colors[idx % self.palette.colors.len()])
+    }
+}
+
+/// Names of the `colorgrad` presets known to `preset`.
+pub const PRESETS: [&str; 20] = [
+    "magma", "inferno", "plasma", "viridis", "cividis", "turbo", "rainbow", "sinebow", "spectral",
+    "rd_bu", "rd_yl_bu", "rd_yl_gn", "br_bg", "pu_or", "blues", "greens", "greys", "oranges",
+    "purples", "reds",
+];
+
+/// The `colorgrad` preset named `name`.
+pub fn preset(name: &str) -> Option<Box<dyn Gradient>> {
+    use colorgrad::preset;
+    Some(match name {
+        "magma" => Box::new(preset::magma()),
+        "inferno" => Box::new(preset::inferno()),
+        "plasma" => Box::new(preset::plasma()),
+        "viridis" => Box::new(preset::viridis()),
+        "cividis" => Box::new(preset::cividis()),
+        "turbo" => Box::new(preset::turbo()),
+        "rainbow" => Box::new(preset::rainbow()),
+        "sinebow" => Box::new(preset::sinebow()),
+        "spectral" => Box::new(preset::spectral()),
+        "rd_bu" => Box::new(preset::rd_bu()),
+        "rd_yl_bu" => Box::new(preset::rd_yl_bu()),
+        "rd_yl_gn" => Box::new(preset::rd_yl_gn()),
+        "br_bg" => Box::new(preset::br_bg()),
+        "pu_or" => Box::new(preset::pu_or()),
+        "blues" => Box::new(preset::blues()),
+        "greens" => Box::new(preset::greens()),
+        "greys" => Box::new(preset::greys()),
+        "oranges" => Box::new(preset::oranges()),
+        "purples" => Box::new(preset::purples()),
+        "reds" => Box::new(preset::reds()),
+        _ => return None,
+    })
+}
+
+impl ColorType {
+    /// The gradient of this color mode, `None` for plain RGB and categories.
+    pub fn gradient(&self) -> Option<&dyn Gradient> {
+        match self {
+            ColorType::RGB | ColorType::Categorical { .. } => None,
+            ColorType::Gradient { generator, .. } => Some(generator.as_ref()),
+        }
+    }
+
+    /// The value channel colored by the gradient, `None` for plain RGB and categories.
+    pub fn channel(&self) -> Option<&str> {
+        match self {
+            ColorType::RGB | ColorType::Categorical { .. } => None,
+            ColorType::Gradient { channel, .. } => Some(channel),
+        }
+    }
+
+    /// The attribute colored by categories, `None` for plain RGB and gradients.
+    pub fn attribute(&self) -> Option<&Attribute> {
+        match self {
+            ColorType::Categorical { attribute, .. } => Some(attribute),
+            _ => None,
+        }
+    }
+
+    /// Hand out the colors of `categories`, sorted and deduplicated, `None` unless categorical.
+    pub fn category_map(&self, categories: &[String]) -> Option<CategoryMap<'_>> {
+        let ColorType::Categorical { palette, .. } = self else {
+            return None;
+        };
+        Some(CategoryMap {
+            palette,
+            index: categories
+                .iter()
+                .enumerate()
+                .map(|(idx, c)| (c.clone(), idx))
+                .collect(),
+        })
+    }
+
+    /// Resolve the value range of the gradient, `None` for plain RGB.
+    /// `global` holds every value of the db and `version` every value on display, both sorted.
+    pub fn color_map(&self, global: &[f32], version: &[f32]) -> Option<ColorMap<'_>> {
+        let ColorType::Gradient {
+            generator,
+            min,
+            max,
+            range,
+            scale,
+            diverging,
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let quantile = |values: &[f32], q: f32| {
+            let idx = (q.clamp(0.0, 1.0) * (values.len() - 1) as f32).round() as usize;
+            values[idx]
+        };
+        let (lo, hi) = match range {
+            ValueRange::Fixed => (*min, *max),
+            ValueRange::Global | ValueRange::Percentile { .. } if global.is_empty() => (*min, *max),
+            ValueRange::PerVersion if version.is_empty() => (*min, *max),
+            ValueRange::Global => (global[0], global[global.len() - 1]),
+            ValueRange::PerVersion => (version[0], version[version.len() - 1]),
+            ValueRange::Percentile { low, high } => {
+                (quantile(global, *low), quantile(global, *high))
+            }
+        };
+        let (lo, hi) = if *diverging {
+            let m = lo.abs().max(hi.abs());
+            (-m, m)
+        } else {
+            (lo, hi)
+        };
+        Some(ColorMap {
+            grad: generator.as_ref(),
+            min: lo,
+            max: hi,
+            scale: *scale,
+        })
+    }
 }
+
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct Color {
     pub r: u8,
@@ -21,12 +296,72 @@ pub struct Color {
 }
 
 impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self {
+            r,
+            g,
+            b,
+            a: u8::MAX,
+        }
+    }
+
+    /// A CSS named color, such as `red` or `steelblue`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphabetic()) {
+            return None;
+        }
+        let [r, g, b, a] = colorgrad::Color::from_html(name).ok()?.to_rgba8();
+        Some(Self { r, g, b, a })
+    }
+
+    /// Parse `#rrggbb` or `#rrggbbaa`.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let digits = hex.strip_prefix('#')?;
+        if !matches!(digits.len(), 6 | 8) || !digits.is_ascii() {
+            return None;
+        }
+        let byte = |i: usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+        Some(Self {
+            r: byte(0)?,
+            g: byte(2)?,
+            b: byte(4)?,
+            a: if digits.len() == 8 { byte(6)? } else { u8::MAX },
+        })
+    }
+
     pub fn from_grad(gradient: f32, color_grad: &colorgrad::BasisGradient) -> Self {
         let res = color_grad.at(gradient.clamp(0.0, 1.0));
         (&res).into()
     }
 }
 
+/// `#rrggbb`, or `#rrggbbaa` when not opaque, as read by `Color::from_hex`.
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != u8::MAX {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+/// Written as by `Display`, read as a hex code or a CSS name, as in scripts.
+impl serde::Serialize for Color {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Color {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Color::from_hex(&s)
+            .or_else(|| Color::from_name(&s))
+            .ok_or_else(|| serde::de::Error::custom(format!("bad color `{}`", s)))
+    }
+}
+
 impl From<&colorgrad::Color> for Color {
     fn from(value: &colorgrad::Color) -> Self {
         Self {
@@ -37,3 +372,51 @@ impl From<&colorgrad::Color> for Color {
         }
     }
 }
+
+#[test]
+fn hex_colors() {
+    assert_eq!(Color::from_hex("#ff8800"), Some(Color::rgb(255, 136, 0)));
+    assert_eq!(
+        Color::from_hex("#FF8800cc"),
+        Some(Color {
+            r: 255,
+            g: 136,
+            b: 0,
+            a: 204
+        })
+    );
+    assert_eq!(
+        Color::from_hex("#FF8800cc").unwrap().to_string(),
+        "#ff8800cc"
+    );
+    assert_eq!(Color::rgb(255, 136, 0).to_string(), "#ff8800");
+    assert_eq!(Color::from_hex("ff8800"), None);
+    assert_eq!(Color::from_hex("#ff88"), None);
+    assert_eq!(Color::from_hex("#gg8800"), None);
+}
+
+#[test]
+fn categories() {
+    let name = b"top/core/alu";
+    assert_eq!(
+        Attribute::Prefix { depth: 2 }.of(name, None, None),
+        Some("top/core".into())
+    );
+    assert_eq!(Attribute::Desc.of(name, None, None), None);
+    assert_eq!(
+        Attribute::Channel("cluster".into()).of(name, None, Some(3.0)),
+        Some("3".into())
+    );
+
+    let color = ColorType::Categorical {
+        palette: palette("set1").unwrap(),
+        attribute: Attribute::Name,
+    };
+    let categories: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+    let map = color.category_map(&categories).unwrap();
+    assert_eq!(map.color("0"), Some(Color::rgb(0xe4, 0x1a, 0x1c)));
+    assert_ne!(map.color("0"), map.color("1"));
+    // Nine colors in `set1`, the tenth category wraps around.
+    assert_eq!(map.color("9"), map.color("0"));
+    assert_eq!(map.color("x"), None);
+}
@@ -0,0 +1,107 @@
+use colorgrad::Gradient;
+
+use super::color::{Color, Scale};
+
+/// A gradient together with the resolved range of values it spans.
+pub struct ColorMap<'a> {
+    pub grad: &'a dyn Gradient,
+    pub min: f32,
+    pub max: f32,
+    pub scale: Scale,
+}
+
+impl ColorMap<'_> {
+    fn transform(&self, v: f32) -> f32 {
+        match self.scale {
+            Scale::Linear => v,
+            Scale::Log => v.signum() * v.abs().ln_1p(),
+        }
+    }
+
+    fn inverse(&self, v: f32) -> f32 {
+        match self.scale {
+            Scale::Linear => v,
+            Scale::Log => v.signum() * v.abs().exp_m1(),
+        }
+    }
+
+    /// Position of `value` along the gradient, clamped into `0..=1`.
+    pub fn position(&self, value: f32) -> f32 {
+        let (lo, hi) = (self.transform(self.min), self.transform(self.max));
+        if hi <= lo {
+            return 0.5;
+        }
+        ((self.transform(value) - lo) / (hi - lo)).clamp(0.0, 1.0)
+    }
+
+    /// The value found at position `t` of the gradient, the inverse of `position`.
+    pub fn value_at(&self, t: f32) -> f32 {
+        let (lo, hi) = (self.transform(self.min), self.transform(self.max));
+        self.inverse(lo + t * (hi - lo))
+    }
+
+    pub fn color(&self, value: f32) -> Color {
+        (&self.grad.at(self.position(value))).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::geo::color::{ColorType, ValueRange};
+
+    use super::*;
+
+    fn gradient(range: ValueRange, scale: Scale, diverging: bool) -> ColorType {
+        ColorType::Gradient {
+            generator: Box::new(colorgrad::preset::magma()),
+            min: 0.0,
+            max: 3000.0,
+            range,
+            scale,
+            diverging,
+            channel: "value".into(),
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        let global = [1000.0, 2000.0, 3000.0, 4000.0, 9000.0];
+        let version = [2000.0, 4000.0];
+        let bounds = |c: &ColorType| {
+            let map = c.color_map(&global, &version).unwrap();
+            (map.min, map.max)
+        };
+        let linear = |range| gradient(range, Scale::Linear, false);
+        assert_eq!(bounds(&linear(ValueRange::Fixed)), (0.0, 3000.0));
+        assert_eq!(bounds(&linear(ValueRange::Global)), (1000.0, 9000.0));
+        assert_eq!(bounds(&linear(ValueRange::PerVersion)), (2000.0, 4000.0));
+        assert_eq!(
+            bounds(&linear(ValueRange::Percentile {
+                low: 0.25,
+                high: 0.75
+            })),
+            (2000.0, 4000.0)
+        );
+        assert!(ColorType::RGB.color_map(&global, &version).is_none());
+    }
+
+    #[test]
+    fn positions() {
+        let linear = gradient(ValueRange::Global, Scale::Linear, false);
+        let map = linear.color_map(&[0.0, 100.0], &[]).unwrap();
+        assert_eq!(map.position(25.0), 0.25);
+        assert_eq!(map.position(-5.0), 0.0);
+        assert_eq!(map.position(500.0), 1.0);
+        assert_eq!(map.value_at(0.5), 50.0);
+
+        let log = gradient(ValueRange::Global, Scale::Log, false);
+        let map = log.color_map(&[0.0, 99.0], &[]).unwrap();
+        assert!((map.position(9.0) - 0.5).abs() < 1e-6);
+        assert!((map.value_at(0.5) - 9.0).abs() < 1e-4);
+
+        let diverging = gradient(ValueRange::Global, Scale::Linear, true);
+        let map = diverging.color_map(&[-10.0, 40.0], &[]).unwrap();
+        assert_eq!((map.min, map.max), (-40.0, 40.0));
+        assert_eq!(map.position(0.0), 0.5);
+    }
+}
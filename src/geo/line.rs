@@ -1,13 +1,143 @@
-use super::point::Point;
+use super::{point::Point, rect::Rect, vector::Vector};
 
+/// A line segment from `ll` to `ur`.
 #[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
 pub struct Line {
     pub ll: Point,
     pub ur: Point,
 }
 
+impl Line {
+    pub const fn new(ll: Point, ur: Point) -> Self {
+        Self { ll, ur }
+    }
+
+    pub fn length(&self) -> f64 {
+        self.ll.euclidean(&self.ur)
+    }
+
+    pub fn manhattan_length(&self) -> f64 {
+        self.ll.manhattan(&self.ur)
+    }
+
+    pub fn center(&self) -> Point {
+        Point::new((self.ll.x + self.ur.x) / 2.0, (self.ll.y + self.ur.y) / 2.0)
+    }
+
+    pub fn bbox(&self) -> Rect {
+        Rect::new(
+            Point::new(self.ll.x.min(self.ur.x), self.ll.y.min(self.ur.y)),
+            Point::new(self.ll.x.max(self.ur.x), self.ll.y.max(self.ur.y)),
+        )
+    }
+
+    pub fn translate(&self, v: Vector) -> Line {
+        Line::new(self.ll + v, self.ur + v)
+    }
+
+    /// Euclidean distance from `p` to the closest point of the segment.
+    pub fn distance(&self, p: &Point) -> f64 {
+        let dx = self.ur.x - self.ll.x;
+        let dy = self.ur.y - self.ll.y;
+        let len2 = dx * dx + dy * dy;
+        if len2 == 0.0 {
+            return self.ll.euclidean(p);
+        }
+        let t = (((p.x - self.ll.x) * dx + (p.y - self.ll.y) * dy) / len2).clamp(0.0, 1.0);
+        Point::new(self.ll.x + t * dx, self.ll.y + t * dy).euclidean(p)
+    }
+
+    /// The part of the line inside `rect`, `None` if the line misses it (Liang-Barsky).
+    pub fn clip(&self, rect: &Rect) -> Option<Line> {
+        let dx = self.ur.x - self.ll.x;
+        let dy = self.ur.y - self.ll.y;
+        let (mut t0, mut t1) = (0.0_f64, 1.0_f64);
+        let edges = [
+            (-dx, self.ll.x - rect.ll.x),
+            (dx, rect.ur.x - self.ll.x),
+            (-dy, self.ll.y - rect.ll.y),
+            (dy, rect.ur.y - self.ll.y),
+        ];
+        for (p, q) in edges {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+                continue;
+            }
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+            if t0 > t1 {
+                return None;
+            }
+        }
+        let at = |t: f64| Point::new(self.ll.x + t * dx, self.ll.y + t * dy);
+        Some(Line::new(at(t0), at(t1)))
+    }
+}
+
 impl From<Line> for egui_plot::PlotPoints {
     fn from(value: Line) -> Self {
         Self::Owned(vec![value.ll.into(), value.ur.into()])
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn line(x0: f64, y0: f64, x1: f64, y1: f64) -> Line {
+        Line::new(Point::new(x0, y0), Point::new(x1, y1))
+    }
+
+    #[test]
+    fn measures() {
+        let l = line(0.0, 0.0, 3.0, -4.0);
+        assert_eq!(l.length(), 5.0);
+        assert_eq!(l.manhattan_length(), 7.0);
+        assert_eq!(l.center(), Point::new(1.5, -2.0));
+        assert_eq!(
+            l.bbox(),
+            Rect::new(Point::new(0.0, -4.0), Point::new(3.0, 0.0))
+        );
+        let v = Point::new(1.0, 1.0) - Point::new(0.0, 0.0);
+        assert_eq!(l.translate(v), line(1.0, 1.0, 4.0, -3.0));
+        assert_eq!(l.distance(&Point::new(0.0, 0.0)), 0.0);
+        assert_eq!(
+            line(0.0, 0.0, 4.0, 0.0).distance(&Point::new(2.0, 3.0)),
+            3.0
+        );
+        assert_eq!(
+            line(0.0, 0.0, 4.0, 0.0).distance(&Point::new(7.0, 4.0)),
+            5.0
+        );
+    }
+
+    #[test]
+    fn clipping() {
+        let r = Rect::new(Point::new(0.0, 0.0), Point::new(2.0, 2.0));
+        // Crossing the whole rect.
+        assert_eq!(
+            line(-1.0, 1.0, 3.0, 1.0).clip(&r),
+            Some(line(0.0, 1.0, 2.0, 1.0))
+        );
+        // Diagonal, starting inside.
+        assert_eq!(
+            line(1.0, 1.0, 4.0, 4.0).clip(&r),
+            Some(line(1.0, 1.0, 2.0, 2.0))
+        );
+        // Fully inside.
+        assert_eq!(
+            line(0.5, 0.5, 1.5, 1.0).clip(&r),
+            Some(line(0.5, 0.5, 1.5, 1.0))
+        );
+        // Missing the rect, parallel and oblique.
+        assert_eq!(line(-1.0, 3.0, 3.0, 3.0).clip(&r), None);
+        assert_eq!(line(3.0, 0.0, 5.0, 2.0).clip(&r), None);
+        assert_eq!(line(1.0, 4.0, 4.0, 1.0).clip(&r), None);
+    }
+}
@@ -1,7 +1,9 @@
 pub mod color;
-pub mod criticality;
+pub mod colormap;
 pub mod line;
 pub mod point;
 pub mod rect;
 pub mod shape;
+pub mod style;
+pub mod validate;
 pub mod vector;
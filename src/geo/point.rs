@@ -1,11 +1,84 @@
+use std::ops::{Add, Sub};
+
+use super::vector::Vector;
+
 #[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
 pub struct Point {
     pub x: f64,
     pub y: f64,
 }
 
+impl Point {
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    pub fn manhattan(&self, other: &Point) -> f64 {
+        (self.x - other.x).abs() + (self.y - other.y).abs()
+    }
+
+    pub fn euclidean(&self, other: &Point) -> f64 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
+
+impl Add<Vector> for Point {
+    type Output = Point;
+
+    fn add(self, rhs: Vector) -> Self::Output {
+        Point::new(self.x + rhs.dx(), self.y + rhs.dy())
+    }
+}
+
+impl Sub<Vector> for Point {
+    type Output = Point;
+
+    fn sub(self, rhs: Vector) -> Self::Output {
+        Point::new(self.x - rhs.dx(), self.y - rhs.dy())
+    }
+}
+
+/// The vector going from `rhs` to `self`.
+impl Sub for Point {
+    type Output = Vector;
+
+    fn sub(self, rhs: Point) -> Self::Output {
+        Vector {
+            init: rhs,
+            term: self,
+        }
+    }
+}
+
 impl From<Point> for egui_plot::PlotPoint {
     fn from(value: Point) -> Self {
         Self::new(value.x, value.y)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        let a = Point::new(1.0, 1.0);
+        let b = Point::new(4.0, -3.0);
+        assert_eq!(a.manhattan(&b), 7.0);
+        assert_eq!(a.euclidean(&b), 5.0);
+        assert_eq!(b.euclidean(&a), 5.0);
+        assert_eq!(a.manhattan(&a), 0.0);
+    }
+
+    #[test]
+    fn ops() {
+        let a = Point::new(1.0, 2.0);
+        let b = Point::new(4.0, 6.0);
+        let v = b - a;
+        assert_eq!(v.init, a);
+        assert_eq!(v.term, b);
+        assert_eq!(a + v, b);
+        assert_eq!(b - v, a);
+        assert_eq!(Point::new(0.0, 0.0) + v, Point::new(3.0, 4.0));
+    }
+}
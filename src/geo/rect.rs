@@ -1,13 +1,202 @@
-use super::point::Point;
+use super::{point::Point, vector::Vector};
 
+/// An axis-aligned rectangle. The geometric operations assume `ll` is to the lower-left of `ur`,
+/// see `Rect::validate`.
 #[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
 pub struct Rect {
     pub ll: Point,
     pub ur: Point,
 }
 
+impl Rect {
+    pub const fn new(ll: Point, ur: Point) -> Self {
+        Self { ll, ur }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.ur.x - self.ll.x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.ur.y - self.ll.y
+    }
+
+    pub fn area(&self) -> f64 {
+        self.width() * self.height()
+    }
+
+    pub fn center(&self) -> Point {
+        Point::new((self.ll.x + self.ur.x) / 2.0, (self.ll.y + self.ur.y) / 2.0)
+    }
+
+    /// Whether `p` is inside the rect or on its boundary.
+    pub fn contains(&self, p: &Point) -> bool {
+        self.ll.x <= p.x && p.x <= self.ur.x && self.ll.y <= p.y && p.y <= self.ur.y
+    }
+
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        self.contains(&other.ll) && self.contains(&other.ur)
+    }
+
+    /// Whether the rects share at least one point, touching boundaries included.
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.ll.x <= other.ur.x
+            && other.ll.x <= self.ur.x
+            && self.ll.y <= other.ur.y
+            && other.ll.y <= self.ur.y
+    }
+
+    /// Whether the rects share a region of positive area.
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.ll.x < other.ur.x
+            && other.ll.x < self.ur.x
+            && self.ll.y < other.ur.y
+            && other.ll.y < self.ur.y
+    }
+
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        if !self.intersects(other) {
+            return None;
+        }
+        Some(Rect::new(
+            Point::new(self.ll.x.max(other.ll.x), self.ll.y.max(other.ll.y)),
+            Point::new(self.ur.x.min(other.ur.x), self.ur.y.min(other.ur.y)),
+        ))
+    }
+
+    /// Bounding box of both rects.
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect::new(
+            Point::new(self.ll.x.min(other.ll.x), self.ll.y.min(other.ll.y)),
+            Point::new(self.ur.x.max(other.ur.x), self.ur.y.max(other.ur.y)),
+        )
+    }
+
+    /// Bounding box of all the rects, `None` if there is none.
+    pub fn bounding(rects: impl IntoIterator<Item = Rect>) -> Option<Rect> {
+        rects.into_iter().reduce(|a, b| a.union(&b))
+    }
+
+    /// Grow the rect by `d` on every side.
+    pub fn expand(&self, d: f64) -> Rect {
+        Rect::new(
+            Point::new(self.ll.x - d, self.ll.y - d),
+            Point::new(self.ur.x + d, self.ur.y + d),
+        )
+    }
+
+    /// Shrink the rect by `d` on every side. A side collapses onto the center instead of inverting.
+    pub fn shrink(&self, d: f64) -> Rect {
+        let dx = d.min(self.width() / 2.0);
+        let dy = d.min(self.height() / 2.0);
+        Rect::new(
+            Point::new(self.ll.x + dx, self.ll.y + dy),
+            Point::new(self.ur.x - dx, self.ur.y - dy),
+        )
+    }
+
+    pub fn translate(&self, v: Vector) -> Rect {
+        Rect::new(self.ll + v, self.ur + v)
+    }
+
+    /// Manhattan distance between the closest points of both rects, zero if they intersect.
+    pub fn manhattan(&self, other: &Rect) -> f64 {
+        let (dx, dy) = self.gap(other);
+        dx + dy
+    }
+
+    /// Euclidean distance between the closest points of both rects, zero if they intersect.
+    pub fn euclidean(&self, other: &Rect) -> f64 {
+        let (dx, dy) = self.gap(other);
+        dx.hypot(dy)
+    }
+
+    fn gap(&self, other: &Rect) -> (f64, f64) {
+        let dx = (other.ll.x - self.ur.x)
+            .max(self.ll.x - other.ur.x)
+            .max(0.0);
+        let dy = (other.ll.y - self.ur.y)
+            .max(self.ll.y - other.ur.y)
+            .max(0.0);
+        (dx, dy)
+    }
+}
+
 impl From<Rect> for egui_plot::PlotPoints {
     fn from(value: Rect) -> Self {
         Self::Owned(vec![value.ll.into(), value.ur.into()])
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rect(llx: f64, lly: f64, urx: f64, ury: f64) -> Rect {
+        Rect::new(Point::new(llx, lly), Point::new(urx, ury))
+    }
+
+    #[test]
+    fn measures() {
+        let r = rect(1.0, 2.0, 4.0, 6.0);
+        assert_eq!(r.width(), 3.0);
+        assert_eq!(r.height(), 4.0);
+        assert_eq!(r.area(), 12.0);
+        assert_eq!(r.center(), Point::new(2.5, 4.0));
+    }
+
+    #[test]
+    fn containment() {
+        let r = rect(0.0, 0.0, 2.0, 2.0);
+        assert!(r.contains(&Point::new(1.0, 1.0)));
+        assert!(r.contains(&Point::new(2.0, 0.0)));
+        assert!(!r.contains(&Point::new(2.1, 1.0)));
+        assert!(r.contains_rect(&rect(0.5, 0.5, 2.0, 1.0)));
+        assert!(!r.contains_rect(&rect(0.5, 0.5, 3.0, 1.0)));
+    }
+
+    #[test]
+    fn intersections() {
+        let a = rect(0.0, 0.0, 2.0, 2.0);
+        let b = rect(1.0, 1.0, 3.0, 3.0);
+        let touching = rect(2.0, 0.0, 3.0, 1.0);
+        let far = rect(5.0, 5.0, 6.0, 6.0);
+
+        assert!(a.intersects(&b) && a.overlaps(&b));
+        assert_eq!(a.intersection(&b), Some(rect(1.0, 1.0, 2.0, 2.0)));
+
+        assert!(a.intersects(&touching) && !a.overlaps(&touching));
+        assert_eq!(a.intersection(&touching).map(|r| r.area()), Some(0.0));
+
+        assert!(!a.intersects(&far));
+        assert_eq!(a.intersection(&far), None);
+    }
+
+    #[test]
+    fn bounding_boxes() {
+        let a = rect(0.0, 0.0, 2.0, 2.0);
+        let b = rect(1.0, -1.0, 3.0, 1.0);
+        assert_eq!(a.union(&b), rect(0.0, -1.0, 3.0, 2.0));
+        assert_eq!(Rect::bounding([a, b]), Some(rect(0.0, -1.0, 3.0, 2.0)));
+        assert_eq!(Rect::bounding([]), None);
+    }
+
+    #[test]
+    fn resize_and_move() {
+        let r = rect(0.0, 0.0, 4.0, 2.0);
+        assert_eq!(r.expand(1.0), rect(-1.0, -1.0, 5.0, 3.0));
+        assert_eq!(r.shrink(0.5), rect(0.5, 0.5, 3.5, 1.5));
+        assert_eq!(r.shrink(2.0), rect(2.0, 1.0, 2.0, 1.0));
+        let v = Point::new(1.0, 1.0) - Point::new(0.0, 0.0);
+        assert_eq!(r.translate(v), rect(1.0, 1.0, 5.0, 3.0));
+    }
+
+    #[test]
+    fn distances() {
+        let a = rect(0.0, 0.0, 1.0, 1.0);
+        let b = rect(4.0, 5.0, 6.0, 6.0);
+        assert_eq!(a.manhattan(&b), 7.0);
+        assert_eq!(a.euclidean(&b), 5.0);
+        assert_eq!(a.euclidean(&rect(0.5, 0.5, 2.0, 2.0)), 0.0);
+    }
+}
@@ -5,3 +5,29 @@ pub enum Shape {
     Line(Line),
     None,
 }
+
+/// Whether an action targets a rect or a line, known even when it carries no geometry.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShapeKind {
+    Rect,
+    Line,
+}
+
+impl ShapeKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ShapeKind::Rect => "rect",
+            ShapeKind::Line => "line",
+        }
+    }
+}
+
+impl Shape {
+    pub fn kind(&self) -> Option<ShapeKind> {
+        match self {
+            Shape::Rect(_) => Some(ShapeKind::Rect),
+            Shape::Line(_) => Some(ShapeKind::Line),
+            Shape::None => None,
+        }
+    }
+}
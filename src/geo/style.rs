@@ -0,0 +1,34 @@
+use super::color::Color;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Fill {
+    Solid,
+    /// Only the stroke is drawn.
+    Outline,
+}
+
+/// How a shape is drawn on top of its color. Unset fields are left to the renderer.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Style {
+    pub stroke: Option<Color>,
+    pub width: Option<f32>,
+    /// Length of the dashes of the stroke, solid when zero.
+    pub dash: Option<f32>,
+    pub fill: Option<Fill>,
+}
+
+impl Style {
+    /// Take over the fields set in `other`, returns whether anything has changed.
+    pub fn merge(&mut self, other: &Style) -> bool {
+        let old = *self;
+        self.stroke = other.stroke.or(self.stroke);
+        self.width = other.width.or(self.width);
+        self.dash = other.dash.or(self.dash);
+        self.fill = other.fill.or(self.fill);
+        old != *self
+    }
+
+    pub fn is_outline(&self) -> bool {
+        self.fill == Some(Fill::Outline)
+    }
+}
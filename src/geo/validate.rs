@@ -0,0 +1,203 @@
+use std::fmt::Display;
+
+use super::{line::Line, point::Point, rect::Rect, shape::Shape};
+
+/// How geometries are checked before they get into a `Db`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Validation {
+    /// Swap the corners of inverted rects instead of rejecting them.
+    pub normalize: bool,
+    /// Accept zero-area rects and zero-length lines, such as pins and markers. Set it to `false` to reject them.
+    pub allow_empty: bool,
+    /// Reject rects whose width or height, and lines whose length, is below this size.
+    pub min_size: Option<f64>,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Self {
+            normalize: true,
+            allow_empty: true,
+            min_size: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GeoIssue {
+    /// A coordinate is NaN or infinite.
+    NonFinite,
+    /// `ll` is not to the lower-left of `ur`.
+    Inverted,
+    /// A rect without area, or a line without length.
+    Empty,
+    /// Smaller than `Validation::min_size`.
+    TooSmall { size: f64 },
+}
+
+impl Display for GeoIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeoIssue::NonFinite => write!(f, "coordinates are not finite"),
+            GeoIssue::Inverted => write!(f, "corners are inverted"),
+            GeoIssue::Empty => write!(f, "geometry is empty"),
+            GeoIssue::TooSmall { size } => write!(f, "size {} is below the minimum", size),
+        }
+    }
+}
+
+fn is_finite(p: &Point) -> bool {
+    p.x.is_finite() && p.y.is_finite()
+}
+
+fn check_size(size: f64, cfg: &Validation) -> Result<(), GeoIssue> {
+    if size == 0.0 && !cfg.allow_empty {
+        return Err(GeoIssue::Empty);
+    }
+    match cfg.min_size {
+        Some(min) if size < min => Err(GeoIssue::TooSmall { size }),
+        _ => Ok(()),
+    }
+}
+
+impl Rect {
+    /// Check the rect against `cfg`.
+    /// Returns the normalized rect, and the issue it was normalized for, or the issue it is rejected for.
+    pub fn validate(self, cfg: &Validation) -> Result<(Self, Option<GeoIssue>), GeoIssue> {
+        if !is_finite(&self.ll) || !is_finite(&self.ur) {
+            return Err(GeoIssue::NonFinite);
+        }
+        let mut res = self;
+        let mut issue = None;
+        if self.ll.x > self.ur.x || self.ll.y > self.ur.y {
+            if !cfg.normalize {
+                return Err(GeoIssue::Inverted);
+            }
+            res = Rect {
+                ll: Point {
+                    x: self.ll.x.min(self.ur.x),
+                    y: self.ll.y.min(self.ur.y),
+                },
+                ur: Point {
+                    x: self.ll.x.max(self.ur.x),
+                    y: self.ll.y.max(self.ur.y),
+                },
+            };
+            issue = Some(GeoIssue::Inverted);
+        }
+        let size = (res.ur.x - res.ll.x).min(res.ur.y - res.ll.y);
+        check_size(size, cfg)?;
+        Ok((res, issue))
+    }
+}
+
+impl Line {
+    /// Check the line against `cfg`. Lines have no orientation to normalize.
+    pub fn validate(self, cfg: &Validation) -> Result<(Self, Option<GeoIssue>), GeoIssue> {
+        if !is_finite(&self.ll) || !is_finite(&self.ur) {
+            return Err(GeoIssue::NonFinite);
+        }
+        let size = (self.ur.x - self.ll.x).hypot(self.ur.y - self.ll.y);
+        check_size(size, cfg)?;
+        Ok((self, None))
+    }
+}
+
+impl Shape {
+    pub fn validate(self, cfg: &Validation) -> Result<(Self, Option<GeoIssue>), GeoIssue> {
+        match self {
+            Shape::Rect(rect) => rect.validate(cfg).map(|(r, i)| (Shape::Rect(r), i)),
+            Shape::Line(line) => line.validate(cfg).map(|(l, i)| (Shape::Line(l), i)),
+            Shape::None => Ok((Shape::None, None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rect(llx: f64, lly: f64, urx: f64, ury: f64) -> Rect {
+        Rect {
+            ll: Point { x: llx, y: lly },
+            ur: Point { x: urx, y: ury },
+        }
+    }
+
+    #[test]
+    fn valid_rect() {
+        let r = rect(0.0, 0.0, 2.0, 1.0);
+        assert_eq!(r.validate(&Validation::default()), Ok((r, None)));
+    }
+
+    #[test]
+    fn inverted_rect() {
+        let r = rect(2.0, 1.0, 0.0, 3.0);
+        assert_eq!(
+            r.validate(&Validation::default()),
+            Ok((rect(0.0, 1.0, 2.0, 3.0), Some(GeoIssue::Inverted)))
+        );
+        let strict = Validation {
+            normalize: false,
+            ..Default::default()
+        };
+        assert_eq!(r.validate(&strict), Err(GeoIssue::Inverted));
+    }
+
+    #[test]
+    fn non_finite_rect() {
+        let cfg = Validation::default();
+        assert_eq!(
+            rect(f64::NAN, 0.0, 1.0, 1.0).validate(&cfg),
+            Err(GeoIssue::NonFinite)
+        );
+        assert_eq!(
+            rect(0.0, 0.0, f64::INFINITY, 1.0).validate(&cfg),
+            Err(GeoIssue::NonFinite)
+        );
+    }
+
+    #[test]
+    fn empty_rect() {
+        let r = rect(0.0, 0.0, 0.0, 1.0);
+        assert_eq!(r.validate(&Validation::default()), Ok((r, None)));
+        let strict = Validation {
+            allow_empty: false,
+            ..Default::default()
+        };
+        assert_eq!(r.validate(&strict), Err(GeoIssue::Empty));
+    }
+
+    #[test]
+    fn small_rect() {
+        let cfg = Validation {
+            min_size: Some(0.5),
+            ..Default::default()
+        };
+        assert_eq!(
+            rect(0.0, 0.0, 1.0, 0.25).validate(&cfg),
+            Err(GeoIssue::TooSmall { size: 0.25 })
+        );
+        assert!(rect(0.0, 0.0, 1.0, 0.5).validate(&cfg).is_ok());
+    }
+
+    #[test]
+    fn lines() {
+        let cfg = Validation {
+            allow_empty: false,
+            min_size: Some(2.0),
+            ..Default::default()
+        };
+        let line = |x: f64, y: f64| Line {
+            ll: Point { x: 0.0, y: 0.0 },
+            ur: Point { x, y },
+        };
+        assert_eq!(line(-3.0, 4.0).validate(&cfg), Ok((line(-3.0, 4.0), None)));
+        assert_eq!(line(0.0, 0.0).validate(&cfg), Err(GeoIssue::Empty));
+        assert_eq!(
+            line(1.0, 0.0).validate(&cfg),
+            Err(GeoIssue::TooSmall { size: 1.0 })
+        );
+        assert_eq!(line(f64::NAN, 0.0).validate(&cfg), Err(GeoIssue::NonFinite));
+    }
+}
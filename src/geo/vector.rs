@@ -1,12 +1,40 @@
-use egui_plot::{Arrows, PlotPoints};
+use egui_plot::{Arrows, PlotPoint, PlotPoints};
 
 use super::point::Point;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Vector {
     pub init: Point,
     pub term: Point,
 }
 
+impl Vector {
+    pub fn dx(&self) -> f64 {
+        self.term.x - self.init.x
+    }
+
+    pub fn dy(&self) -> f64 {
+        self.term.y - self.init.y
+    }
+
+    pub fn manhattan(&self) -> f64 {
+        self.init.manhattan(&self.term)
+    }
+
+    pub fn euclidean(&self) -> f64 {
+        self.init.euclidean(&self.term)
+    }
+
+    /// Batch many vectors into a single plot item.
+    pub fn arrows(vectors: impl IntoIterator<Item = Vector>) -> Arrows {
+        let (init, term): (Vec<_>, Vec<_>) = vectors
+            .into_iter()
+            .map(|v| (PlotPoint::from(v.init), PlotPoint::from(v.term)))
+            .unzip();
+        Arrows::new(PlotPoints::Owned(init), PlotPoints::Owned(term))
+    }
+}
+
 impl From<Vector> for egui_plot::Arrows {
     fn from(value: Vector) -> Self {
         Arrows::new(
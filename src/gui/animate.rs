@@ -0,0 +1,204 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::render::{write_png, DbColors, Picture, RenderOptions};
+use crate::{
+    db::{r#impl::Db, version_controller::VersionId},
+    geo::rect::Rect,
+};
+
+/// Which versions of a db are played, and how every frame looks.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AnimationOptions {
+    pub from: VersionId,
+    pub to: VersionId,
+    /// Number of versions between two frames. The last version is always shown.
+    pub step: u32,
+    /// How long a frame is shown, in milliseconds.
+    pub delay: u32,
+    /// Draw the version number and the commit message on every frame.
+    pub caption: bool,
+    /// A viewport left to `None` is fixed to the area every version of the range fits in,
+    /// so that the layout does not jump between frames.
+    pub render: RenderOptions,
+}
+
+impl AnimationOptions {
+    /// The versions shown, in order.
+    pub fn versions(&self) -> Vec<VersionId> {
+        let mut res: Vec<_> = (self.from.0..=self.to.0)
+            .step_by(self.step.max(1) as usize)
+            .map(VersionId)
+            .collect();
+        if self.from <= self.to && res.last() != Some(&self.to) {
+            res.push(self.to);
+        }
+        res
+    }
+}
+
+/// Frames of a version range of a db, each one a `Picture` of `Db::slice` at that version.
+pub struct Animation<'a> {
+    db: &'a Db,
+    /// Computed once for all the frames.
+    colors: DbColors,
+    opts: AnimationOptions,
+}
+
+impl<'a> Animation<'a> {
+    pub fn new(db: &'a Db, mut opts: AnimationOptions) -> Self {
+        if opts.render.viewport.is_none() {
+            opts.render.viewport = bounds(db, opts.from, opts.to);
+        }
+        Self {
+            db,
+            colors: DbColors::new(db),
+            opts,
+        }
+    }
+
+    pub fn frame(&self, version: VersionId) -> Picture {
+        let mut picture =
+            Picture::version_colored(self.db, &self.colors, version, &self.opts.render);
+        if self.opts.caption {
+            picture.caption.push(format!("version {}", version.0));
+            if let Some(message) = self.db.message(version) {
+                picture.caption.extend(message.lines().map(str::to_owned));
+            }
+        }
+        picture
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = (VersionId, Picture)> + '_ {
+        self.opts.versions().into_iter().map(|v| (v, self.frame(v)))
+    }
+
+    /// Write an animated GIF which loops forever.
+    /// GIF images are at most 65535 pixels wide and high, larger sizes are an error.
+    pub fn write_gif(&self, out: impl io::Write) -> Result<(), gif::EncodingError> {
+        let (width, height) = (self.opts.render.width, self.opts.render.height);
+        let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "a GIF is at most {} pixels wide and high, not {}x{}",
+                    u16::MAX,
+                    width,
+                    height
+                ),
+            )
+            .into());
+        };
+        let mut encoder = gif::Encoder::new(out, width, height, &[])?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for (_, picture) in self.frames() {
+            let mut rgba = picture.raster();
+            let mut frame = gif::Frame::from_rgba_speed(width, height, &mut rgba, 10);
+            frame.delay = (self.opts.delay / 10).min(u16::MAX as u32) as u16;
+            encoder.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    /// Write every frame to `dir` as `frame_00000.png`, `frame_00001.png`, ...,
+    /// ready for an external video encoder. Returns the written files.
+    pub fn write_pngs(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(dir)?;
+        let mut res = vec![];
+        for (idx, (_, picture)) in self.frames().enumerate() {
+            let path = dir.join(format!("frame_{:05}.png", idx));
+            let file = io::BufWriter::new(std::fs::File::create(&path)?);
+            write_png(file, picture.width, picture.height, &picture.raster())
+                .map_err(io::Error::other)?;
+            res.push(path);
+        }
+        Ok(res)
+    }
+
+    /// Write a GIF if `path` ends in `.gif`, or a sequence of PNG frames into the directory `path` otherwise.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if path.extension().is_some_and(|e| e == "gif") {
+            let file = io::BufWriter::new(std::fs::File::create(path)?);
+            self.write_gif(file).map_err(io::Error::other)
+        } else {
+            self.write_pngs(path).map(|_| ())
+        }
+    }
+}
+
+/// Area every shape alive in some version of `from..=to` fits in, with a small margin.
+fn bounds(db: &Db, from: VersionId, to: VersionId) -> Option<Rect> {
+    let rects = db.rects.values().flat_map(|h| {
+        h.0.range(from..=to)
+            .filter_map(|(_, r)| r.as_ref().map(|r| r.geo))
+            .chain(h.query(from).map(|r| r.geo))
+    });
+    let lines = db.lines.values().flat_map(|h| {
+        h.0.range(from..=to)
+            .filter_map(|(_, l)| l.as_ref().map(|l| l.geo.bbox()))
+            .chain(h.query(from).map(|l| l.geo.bbox()))
+    });
+    let bbox = Rect::bounding(rects.chain(lines))?;
+    Some(bbox.expand(0.02 * bbox.width().max(bbox.height())))
+}
+
+#[test]
+fn animate_range() {
+    use crate::{geo::point::Point, gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT \"place A\"\nADDRECT A 0 0 1 1 ;\nCOMMIT\nADDRECT B 3 3 4 4 ;\nCOMMIT \"move A\"\nUPDRECT A 1 1 2 2 ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    assert_eq!(db.message(VersionId(1)), Some("place A"));
+    assert_eq!(db.message(VersionId(2)), None);
+    assert_eq!(db.message(VersionId(3)), Some("move A"));
+
+    let opts = AnimationOptions {
+        from: VersionId(1),
+        to: VersionId(3),
+        step: 2,
+        delay: 100,
+        caption: true,
+        render: RenderOptions {
+            width: 64,
+            height: 64,
+            ..Default::default()
+        },
+    };
+    assert_eq!(opts.versions(), [VersionId(1), VersionId(3)]);
+    let step_3 = AnimationOptions { step: 3, ..opts };
+    assert_eq!(step_3.versions(), [VersionId(1), VersionId(3)]);
+
+    // The viewport holds B, although it only shows up after the first frame.
+    let animation = Animation::new(&db, opts);
+    let first = animation.frame(VersionId(1));
+    assert!(first.viewport.contains(&Point::new(4.0, 4.0)));
+    assert_eq!(first.caption, ["version 1", "place A"]);
+    assert_eq!(animation.frame(VersionId(2)).caption, ["version 2"]);
+    let plain = Animation::new(
+        &db,
+        AnimationOptions {
+            caption: false,
+            ..opts
+        },
+    );
+    assert_ne!(first.raster(), plain.frame(VersionId(1)).raster());
+
+    let mut gif = vec![];
+    animation.write_gif(&mut gif).unwrap();
+    assert_eq!(&gif[..6], b"GIF89a");
+    let mut wide = opts;
+    wide.render.width = 70_000;
+    assert!(Animation::new(&db, wide).write_gif(vec![]).is_err());
+
+    let dir = std::env::temp_dir().join(format!("geo_git_frames_{}", std::process::id()));
+    let files = animation.write_pngs(&dir).unwrap();
+    assert_eq!(files.len(), 2);
+    assert!(files[1].ends_with("frame_00001.png"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
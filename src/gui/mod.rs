@@ -1 +1,4 @@
+pub mod animate;
 pub mod public;
+pub mod render;
+pub mod scene;
@@ -1,81 +1,1130 @@
-use crate::{db::r#impl::Db, geo::color::ColorType};
+use std::{collections::BTreeSet, path::PathBuf, sync::Arc};
+
+use crate::{
+    analysis::{search::Matcher, stats::VersionStats},
+    db::{
+        error::ConflictPolicy,
+        r#impl::{Db, Values},
+        version_controller::VersionId,
+    },
+    geo::{
+        color::{self, Attribute, Color, ColorType, Palette, PALETTES, PRESETS},
+        colormap::ColorMap,
+        point::Point,
+        rect::Rect,
+        style::Style,
+        validate::Validation,
+        vector::Vector,
+    },
+    gui::scene::{color32, gradient_channel, Batch, BatchItem, LayerStyle, Scene, SceneKey},
+    ingest::{Ingest, POLL},
+    settings::Settings,
+};
+use bytes::Bytes;
 use eframe::egui::{self, Color32, Stroke};
-use egui_plot::{Plot, PlotPoints, Polygon};
+use egui_plot::{Plot, PlotBounds, PlotPoint, PlotPoints, Polygon};
 pub struct ToPlot {
     pub x: Db,
     pub version: usize,
+    /// Bounds of the version slider, from the genesis to `Db::version`.
     pub min: usize,
     pub max: usize,
+    /// Step through the versions on every frame.
+    pub playing: bool,
+    /// Playback speed, in commits per second.
+    pub rate: f32,
+    /// Start over from `min` when playback reaches `max`.
+    pub looping: bool,
+    /// Fraction of a commit played since the last step.
+    pub play_clock: f32,
+    /// Draw the regions where rects overlap in red.
+    pub show_overlaps: bool,
+    /// Draw the bin density of the rects over the die.
+    pub show_density: bool,
+    /// Number of density bins along x and y.
+    pub density_bins: (usize, usize),
+    /// Show the per-version statistics next to the canvas.
+    pub show_stats: bool,
+    /// Statistics of every version, computed on first show.
+    pub stats: Option<Vec<VersionStats>>,
+    /// Draw arrows from the rect centers at `displacement_from` to the current ones.
+    pub show_displacement: bool,
+    pub displacement_from: usize,
+    /// Every value of a channel of the db, sorted, computed on first use to find gradient ranges.
+    pub values: Option<(Option<usize>, Vec<f32>)>,
+    /// The `colorgrad` preset picked in the GUI, `None` while the configured gradient is used.
+    pub preset: Option<&'static str>,
+    /// The configured coloring, put aside while categories picked in the GUI are shown.
+    pub stashed_color: Option<ColorType>,
+    /// Every category of an attribute of the db, sorted, computed on first use.
+    pub categories: Option<(Attribute, Vec<String>)>,
+    /// Names of the selected shapes.
+    pub selected: BTreeSet<Bytes>,
+    /// Name pattern of the search box, a glob unless `search_regex`.
+    pub search: String,
+    pub search_regex: bool,
+    /// Outcome of the last search.
+    pub search_status: String,
+    /// Area the canvas zooms to on the next frame.
+    pub zoom_to: Option<Rect>,
+    /// Where the rubber band being dragged started.
+    pub band_start: Option<PlotPoint>,
+    /// What is drawn for the version on display, rebuilt only when it changes.
+    pub scene: Option<Scene>,
+    /// Where the main db was loaded from.
+    pub label: String,
+    /// Draw the main db, which can be hidden to look at the datasets laid over it.
+    pub show_main: bool,
+    /// Dbs loaded next to the main one.
+    pub datasets: Vec<Dataset>,
+    /// Plots shown next to the main one, panned and zoomed together with it.
+    pub panes: Vec<Pane>,
+    /// Commits read while the GUI runs, applied to the main db as they come.
+    pub incoming: Option<Ingest>,
+    /// How the db took the commits of `incoming`.
+    pub applied: Applied,
+    /// Settings the GUI was opened with, for the keys and the limits.
+    pub settings: Settings,
+    /// Where "save view" writes the settings, with the current coloring, overlays and viewport.
+    pub settings_path: PathBuf,
+    /// Outcome of the last save.
+    pub settings_status: String,
+    /// Area the main plot showed on the last frame.
+    pub bounds: Option<Rect>,
+    /// Area the canvas shows, as it is, on the next frame, such as the configured viewport.
+    pub restore: Option<Rect>,
+}
+
+/// How the db took the commits read while the GUI runs, shown on the status line of the source.
+#[derive(Default)]
+pub struct Applied {
+    /// Commits rejected by `Config::policy`.
+    pub rejected: usize,
+    /// Actions skipped or fixed on the way in.
+    pub warnings: usize,
+    /// The last rejection or warning.
+    pub last: Option<String>,
+}
+
+/// A db loaded from a script of its own.
+pub struct Dataset {
+    /// Where the db was loaded from.
+    pub label: String,
+    pub db: Db,
+    /// Lay the dataset over the main plot, at the version on display or its last one.
+    pub visible: bool,
+    /// Draw the dataset as outlines of this color, or with its own coloring if `None`.
+    pub outline: Option<Color>,
+    /// Same as the fields of `ToPlot`, for the db of the dataset.
+    pub values: Option<(Option<usize>, Vec<f32>)>,
+    pub categories: Option<(Attribute, Vec<String>)>,
+    pub scene: Option<Scene>,
+}
+
+impl Dataset {
+    /// A hidden dataset, outlined with the `idx`-th color of the default palette once shown.
+    pub fn new(label: String, db: Db, idx: usize) -> Self {
+        let colors = PALETTES[0].colors;
+        Self {
+            label,
+            db,
+            visible: false,
+            outline: Some(colors[idx % colors.len()]),
+            values: None,
+            categories: None,
+            scene: None,
+        }
+    }
+
+    /// The shapes laid over the main plot for `key`.
+    fn shapes(&mut self, key: SceneKey) -> Arc<Batch> {
+        let key = SceneKey {
+            version: key.version.min(self.db.version),
+            head: self.db.version,
+            overlaps: false,
+            density: None,
+            displacement_from: None,
+            outline: self.outline,
+        };
+        build_scene(
+            &self.db,
+            key,
+            &mut self.scene,
+            &mut self.values,
+            &mut self.categories,
+        );
+        self.scene.as_ref().unwrap().shapes.clone()
+    }
+}
+
+/// Visibility and drawing of the main db and of every dataset.
+fn datasets_panel(ui: &mut egui::Ui, label: &str, show_main: &mut bool, datasets: &mut [Dataset]) {
+    ui.heading("datasets");
+    ui.checkbox(show_main, label);
+    for (idx, dataset) in datasets.iter_mut().enumerate() {
+        ui.separator();
+        ui.checkbox(&mut dataset.visible, &dataset.label);
+        ui.horizontal(|ui| {
+            let mut outlined = dataset.outline.is_some();
+            egui::ComboBox::from_id_source(("overlay", idx))
+                .selected_text(if outlined { "outlines" } else { "fills" })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut outlined, false, "fills");
+                    ui.selectable_value(&mut outlined, true, "outlines");
+                });
+            match (outlined, dataset.outline) {
+                (false, _) => dataset.outline = None,
+                (true, None) => dataset.outline = Some(PALETTES[0].colors[0]),
+                (true, Some(c)) => {
+                    let mut picked = color32(c);
+                    egui::color_picker::color_edit_button_srgba(
+                        ui,
+                        &mut picked,
+                        egui::color_picker::Alpha::Opaque,
+                    );
+                    let [r, g, b, _] = picked.to_srgba_unmultiplied();
+                    dataset.outline = Some(Color::rgb(r, g, b));
+                }
+            }
+        });
+    }
+}
+
+/// A plot of a version of the main db, or of a dataset, next to the main plot.
+pub struct Pane {
+    /// Index in `ToPlot::datasets` of the db shown, the main db if `None`.
+    pub dataset: Option<usize>,
+    pub version: usize,
+    /// Same as the fields of `ToPlot`, for the db of the pane.
+    pub values: Option<(Option<usize>, Vec<f32>)>,
+    pub categories: Option<(Attribute, Vec<String>)>,
+    pub scene: Option<Scene>,
+}
+
+impl Pane {
+    pub fn new(dataset: Option<usize>, version: usize) -> Self {
+        Self {
+            dataset,
+            version,
+            values: None,
+            categories: None,
+            scene: None,
+        }
+    }
+
+    /// Version slider and plot of the pane. Returns `false` once the pane is closed.
+    fn show(&mut self, ui: &mut egui::Ui, idx: usize, db: &Db, label: &str, key: SceneKey) -> bool {
+        let mut open = true;
+        ui.horizontal(|ui| {
+            if ui.small_button("x").on_hover_text("close pane").clicked() {
+                open = false;
+            }
+            ui.label(label);
+            let max = db.version.0 as usize;
+            ui.add(egui::Slider::new(&mut self.version, 0..=max).text("version"));
+        });
+        let key = SceneKey {
+            version: VersionId(self.version as u32),
+            head: db.version,
+            displacement_from: None,
+            ..key
+        };
+        build_scene(
+            db,
+            key,
+            &mut self.scene,
+            &mut self.values,
+            &mut self.categories,
+        );
+        let scene = self.scene.as_ref().unwrap();
+        let plot = linked(Plot::new(("pane", idx))).show(ui, |plot_ui| {
+            plot_ui.add(BatchItem(scene.shapes.clone()));
+            plot_ui.add(BatchItem(scene.overlays.clone()));
+            let tolerance = 4.0 * plot_ui.transform().dvalue_dpos()[0].abs();
+            (plot_ui.pointer_coordinate(), tolerance)
+        });
+        if let (Some(p), true) = (plot.inner.0, plot.response.hovered()) {
+            if let Some(name) = scene
+                .sliced
+                .hit(&Point::new(p.x, p.y), plot.inner.1)
+                .first()
+            {
+                egui::show_tooltip_at_pointer(ui.ctx(), egui::Id::new("hovered"), |ui| {
+                    shape_info(ui, db, key.version, name)
+                });
+            }
+        }
+        open
+    }
+}
+
+/// Share pan, zoom and the cursor crosshair between the main plot and the panes.
+fn linked(plot: Plot) -> Plot {
+    plot.link_axis("panes", true, true)
+        .link_cursor("panes", true, true)
+}
+
+/// Build the scene of `db` for `key` into `scene`, unless it already is.
+/// `values` and `categories` cache the sorted values and categories of the whole db.
+fn build_scene(
+    db: &Db,
+    key: SceneKey,
+    scene: &mut Option<Scene>,
+    values: &mut Option<(Option<usize>, Vec<f32>)>,
+    categories: &mut Option<(Attribute, Vec<String>)>,
+) {
+    if scene.as_ref().is_some_and(|s| s.key == key) {
+        return;
+    }
+    let sliced = db.slice(key.version);
+    let channel = gradient_channel(db);
+    if values.as_ref().map(|(c, _)| *c) != Some(channel) {
+        let all = channel.map(|c| db.values(c)).unwrap_or_default();
+        *values = Some((channel, all));
+    }
+    if let Some(attribute) = db.config.color.attribute() {
+        if categories.as_ref().map(|(a, _)| a) != Some(attribute) {
+            *categories = Some((attribute.clone(), db.categories(attribute)));
+        }
+    }
+    let global = &values.as_ref().unwrap().1;
+    let version_values = channel.map(|c| sliced.values(c)).unwrap_or_default();
+    let categories = categories.as_ref().map_or(&[][..], |(_, c)| c);
+    let shapes = match key.outline {
+        Some(color) => Batch::outlined(&sliced, color),
+        None => Batch::colored(db, &sliced, global, &version_values, categories),
+    };
+    let overlays = Batch::overlays(db, &sliced, key.density, key.overlaps);
+    let arrows = match key.displacement_from {
+        Some(from) => db
+            .displacement(from, key.version)
+            .moves
+            .iter()
+            .filter(|d| d.shift.manhattan() > 0.0)
+            .map(|d| d.shift)
+            .collect(),
+        None => vec![],
+    };
+
+    *scene = Some(Scene {
+        key,
+        sliced,
+        values: version_values,
+        shapes: Arc::new(shapes),
+        overlays: Arc::new(overlays),
+        arrows,
+    });
+}
+
+fn swatch(ui: &mut egui::Ui, c: Color) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(14.0, 14.0), egui::Sense::hover());
+    ui.painter().rect_filled(rect, 0.0, color32(c));
+}
+
+fn rect_points(rect: &Rect) -> PlotPoints {
+    let ll = egui_plot::PlotPoint::new(rect.ll.x, rect.ll.y);
+    let lr = egui_plot::PlotPoint::new(rect.ur.x, rect.ll.y);
+    let ur = egui_plot::PlotPoint::new(rect.ur.x, rect.ur.y);
+    let ul = egui_plot::PlotPoint::new(rect.ll.x, rect.ur.y);
+    PlotPoints::Owned(vec![ll, lr, ur, ul])
+}
+
+type Metric = (&'static str, fn(&VersionStats) -> f64);
+
+const METRICS: [&[Metric]; 5] = [
+    &[("rects", |s| s.rects as f64), ("lines", |s| s.lines as f64)],
+    &[("area", |s| s.area), ("overlap", |s| s.overlap)],
+    &[("utilization", |s| s.utilization())],
+    &[
+        ("added", |s| s.added as f64),
+        ("modified", |s| s.modified as f64),
+        ("deleted", |s| s.deleted as f64),
+    ],
+    &[("displacement", |s| s.displacement)],
+];
+
+fn stats_panel(ui: &mut egui::Ui, stats: &[VersionStats], version: usize) {
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for (idx, metrics) in METRICS.iter().enumerate() {
+            Plot::new(("stats", idx))
+                .height(140.0)
+                .legend(egui_plot::Legend::default())
+                .show(ui, |plot_ui| {
+                    for (name, metric) in metrics.iter() {
+                        let points: PlotPoints = stats
+                            .iter()
+                            .map(|s| [s.version.0 as f64, metric(s)])
+                            .collect();
+                        plot_ui.line(egui_plot::Line::new(points).name(name));
+                    }
+                    plot_ui.vline(egui_plot::VLine::new(version as f64).color(Color32::GRAY));
+                });
+        }
+    });
+}
+
+/// Pick the value channel and the preset of a gradient. Returns whether anything changed.
+fn gradient_controls(ui: &mut egui::Ui, db: &mut Db, preset: &mut Option<&'static str>) -> bool {
+    let Db {
+        channels, config, ..
+    } = db;
+    let ColorType::Gradient {
+        generator, channel, ..
+    } = &mut config.color
+    else {
+        return false;
+    };
+    let mut changed = false;
+    egui::ComboBox::from_label("channel")
+        .selected_text(channel.as_str())
+        .show_ui(ui, |ui| {
+            for c in channels.iter() {
+                changed |= ui.selectable_value(channel, c.clone(), c).changed();
+            }
+        });
+    egui::ComboBox::from_label("gradient")
+        .selected_text(preset.unwrap_or("configured"))
+        .show_ui(ui, |ui| {
+            for name in PRESETS {
+                if ui.selectable_label(*preset == Some(name), name).clicked() {
+                    *preset = Some(name);
+                    *generator = color::preset(name).unwrap();
+                    changed = true;
+                }
+            }
+        });
+    changed
+}
+
+fn attribute_label(attribute: &Attribute) -> String {
+    match attribute {
+        Attribute::Name => "name".into(),
+        Attribute::Prefix { .. } => "name prefix".into(),
+        Attribute::Desc => "description".into(),
+        Attribute::Channel(c) => format!("channel {}", c),
+    }
+}
+
+/// Pick the attribute, and the palette, of categorical coloring.
+/// Going back to `default` restores the configured coloring. Returns whether anything changed.
+fn categorical_controls(ui: &mut egui::Ui, db: &mut Db, stashed: &mut Option<ColorType>) -> bool {
+    let current = db.config.color.attribute().cloned();
+    let depth = match current {
+        Some(Attribute::Prefix { depth }) => depth,
+        _ => 1,
+    };
+    let mut attributes = vec![
+        Attribute::Name,
+        Attribute::Prefix { depth },
+        Attribute::Desc,
+    ];
+    attributes.extend(db.channels.iter().map(|c| Attribute::Channel(c.clone())));
+    let mut picked = current.clone();
+    egui::ComboBox::from_label("color by")
+        .selected_text(current.as_ref().map_or("default".into(), attribute_label))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut picked, None, "default");
+            for attribute in attributes {
+                let text = attribute_label(&attribute);
+                ui.selectable_value(&mut picked, Some(attribute), text);
+            }
+        });
+    let mut changed = picked != current;
+    if changed {
+        match picked {
+            None => db.config.color = stashed.take().unwrap_or_default(),
+            Some(attribute) => match &mut db.config.color {
+                ColorType::Categorical { attribute: a, .. } => *a = attribute,
+                color => {
+                    let categorical = ColorType::Categorical {
+                        palette: &PALETTES[0],
+                        attribute,
+                    };
+                    *stashed = Some(std::mem::replace(color, categorical));
+                }
+            },
+        }
+    }
+    if let ColorType::Categorical { palette, attribute } = &mut db.config.color {
+        if let Attribute::Prefix { depth } = attribute {
+            let depth = egui::DragValue::new(depth)
+                .clamp_range(1..=16)
+                .prefix("depth: ");
+            changed |= ui.add(depth).changed();
+        }
+        egui::ComboBox::from_label("palette")
+            .selected_text(palette.name)
+            .show_ui(ui, |ui| {
+                for p in PALETTES.iter() {
+                    changed |= ui.selectable_value(palette, p, p.name).changed();
+                }
+            });
+    }
+    changed
+}
+
+/// Draw a swatch for each of the first categories, with their names.
+/// Colors are handed out as by `ColorType::category_map`.
+fn category_legend(ui: &mut egui::Ui, palette: &Palette, categories: &[String], shown: usize) {
+    ui.horizontal_wrapped(|ui| {
+        for (idx, category) in categories.iter().enumerate().take(shown) {
+            swatch(ui, palette.colors[idx % palette.colors.len()]);
+            ui.label(category);
+        }
+        if categories.len() > shown {
+            ui.label(format!("and {} more", categories.len() - shown));
+        }
+    });
+}
+
+/// Draw the gradient as a strip, labelled with the values at both ends and in the middle.
+fn colorbar(ui: &mut egui::Ui, map: &ColorMap) {
+    const STEPS: usize = 64;
+    ui.horizontal(|ui| {
+        ui.label(format!("{:.3}", map.min));
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(256.0, 14.0), egui::Sense::hover());
+        let w = rect.width() / STEPS as f32;
+        for i in 0..STEPS {
+            let t = (i as f32 + 0.5) / STEPS as f32;
+            let c = Color::from(&map.grad.at(t));
+            let left = rect.left() + w * i as f32;
+            let step = egui::Rect::from_min_max(
+                egui::pos2(left, rect.top()),
+                egui::pos2(left + w, rect.bottom()),
+            );
+            ui.painter()
+                .rect_filled(step, 0.0, Color32::from_rgb(c.r, c.g, c.b));
+        }
+        ui.label(format!("{:.3}", map.max));
+        ui.label(format!("(mid {:.3})", map.value_at(0.5)));
+    });
+}
+
+fn info_rows(
+    ui: &mut egui::Ui,
+    db: &Db,
+    color: Option<Color>,
+    desc: Option<&Bytes>,
+    values: &Values,
+    style: &Style,
+) {
+    match color {
+        Some(c) => {
+            ui.horizontal(|ui| {
+                ui.label("color:");
+                swatch(ui, c);
+                ui.label(c.to_string());
+            });
+        }
+        None => {
+            ui.label("color: none");
+        }
+    }
+    if let Some(desc) = desc {
+        ui.label(format!("desc: {}", String::from_utf8_lossy(desc)));
+    }
+    for (channel, value) in values.0.iter() {
+        ui.label(format!("{}: {}", db.channels[*channel], value));
+    }
+    if let Some(c) = style.stroke {
+        ui.label(format!("stroke: {}", c));
+    }
+    if let Some(width) = style.width {
+        ui.label(format!("width: {}", width));
+    }
+    if let Some(dash) = style.dash {
+        ui.label(format!("dash: {}", dash));
+    }
+    if style.is_outline() {
+        ui.label("style: outline");
+    }
+}
+
+/// Everything known about the shape `name` at `version`.
+fn shape_info(ui: &mut egui::Ui, db: &Db, version: VersionId, name: &Bytes) {
+    ui.strong(String::from_utf8_lossy(name));
+    if let Some(r) = db.rects.get(name).and_then(|h| h.query(version)) {
+        let g = r.geo;
+        ui.label(format!(
+            "rect ({}, {}) - ({}, {})",
+            g.ll.x, g.ll.y, g.ur.x, g.ur.y
+        ));
+        ui.label(format!("size: {} x {}", g.width(), g.height()));
+        info_rows(ui, db, r.color, r.desc.as_ref(), &r.values, &r.style);
+    } else if let Some(l) = db.lines.get(name).and_then(|h| h.query(version)) {
+        let g = l.geo;
+        ui.label(format!(
+            "line ({}, {}) - ({}, {})",
+            g.ll.x, g.ll.y, g.ur.x, g.ur.y
+        ));
+        ui.label(format!("length: {}", g.length()));
+        info_rows(ui, db, l.color, l.desc.as_ref(), &l.values, &l.style);
+    } else {
+        ui.weak("not alive in this version");
+    }
+}
+
+fn selection_panel(ui: &mut egui::Ui, db: &Db, version: VersionId, selected: &mut BTreeSet<Bytes>) {
+    ui.horizontal(|ui| {
+        ui.heading(format!("{} selected", selected.len()));
+        if ui.button("clear").clicked() {
+            selected.clear();
+        }
+    });
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for name in selected.iter() {
+            ui.separator();
+            shape_info(ui, db, version, name);
+        }
+    });
+}
+
+/// A dot telling whether the source is connected, with the number of writers and of commits,
+/// and what went wrong reading or applying them.
+fn ingest_status(ui: &mut egui::Ui, ingest: &Ingest, applied: &Applied) {
+    let status = ingest.status.borrow();
+    let (color, state) = match (&status.error, status.closed, status.connections) {
+        (Some(_), _, _) => (Color32::RED, "error"),
+        (None, true, _) => (Color32::GRAY, "closed"),
+        (None, false, 0) => (Color32::YELLOW, "waiting"),
+        (None, false, _) => (Color32::GREEN, "connected"),
+    };
+    ui.horizontal(|ui| {
+        ui.colored_label(color, "●");
+        ui.label(format!(
+            "{}: {}, {} writers, {} commits",
+            status.source, state, status.connections, status.received
+        ));
+        let queued = ingest.commits.len();
+        if queued > 0 {
+            ui.label(format!("({} queued)", queued));
+        }
+        if applied.rejected > 0 || applied.warnings > 0 {
+            ui.label(format!(
+                "{} rejected, {} warnings",
+                applied.rejected, applied.warnings
+            ));
+        }
+        if let Some(e) = &status.error {
+            ui.colored_label(Color32::RED, e);
+        }
+        if let Some(last) = &applied.last {
+            ui.colored_label(Color32::YELLOW, last);
+        }
+    });
+}
+
+/// The rect spanned by a rubber band dragged from `start` to `end`.
+fn band(start: PlotPoint, end: PlotPoint) -> Rect {
+    Rect::new(
+        Point::new(start.x.min(end.x), start.y.min(end.y)),
+        Point::new(start.x.max(end.x), start.y.max(end.y)),
+    )
+}
+
+/// Plot bounds showing `area` with a margin around it.
+fn zoom_bounds(area: &Rect) -> PlotBounds {
+    let area = area.expand(0.1 * area.width().max(area.height()).max(1.0));
+    PlotBounds::from_min_max([area.ll.x, area.ll.y], [area.ur.x, area.ur.y])
+}
+
+impl ToPlot {
+    /// Build the scene for `key`, unless the current one already is.
+    fn refresh_scene(&mut self, key: SceneKey) {
+        build_scene(
+            &self.x,
+            key,
+            &mut self.scene,
+            &mut self.values,
+            &mut self.categories,
+        );
+    }
+
+    /// Apply the commits received since the last frame, at most `Limits::commits_per_frame`,
+    /// and follow them if the last version was on display.
+    fn receive(&mut self, ctx: &egui::Context) {
+        let Some(incoming) = &mut self.incoming else {
+            return;
+        };
+        let at_head = self.version == self.x.version.0 as usize;
+        let mut received = 0;
+        while received < self.settings.limits.commits_per_frame.max(1) {
+            let Ok(commit) = incoming.commits.try_recv() else {
+                break;
+            };
+            match self.x.create_version(commit) {
+                Ok(warnings) => {
+                    self.applied.warnings += warnings.len();
+                    if let Some(w) = warnings.last() {
+                        self.applied.last = Some(format!("warning: {}", w));
+                    }
+                }
+                Err(e) => {
+                    self.applied.rejected += 1;
+                    self.applied.last = Some(format!("rejected: {}", e));
+                }
+            }
+            received += 1;
+        }
+        if received > 0 {
+            // The whole db changed, not only the version on display.
+            self.stats = None;
+            self.values = None;
+            self.categories = None;
+            if at_head {
+                self.version = self.x.version.0 as usize;
+            }
+        }
+        ctx.request_repaint_after(POLL);
+    }
+
+    /// Move `delta` versions, within `min..=max`.
+    fn step(&mut self, delta: isize) {
+        self.version = self
+            .version
+            .saturating_add_signed(delta)
+            .clamp(self.min, self.max);
+    }
+
+    /// Advance playback by the time since the last frame.
+    fn play(&mut self, ctx: &egui::Context) {
+        if !self.playing {
+            return;
+        }
+        // A long frame, such as the first one, should not skip ahead.
+        self.play_clock += ctx.input(|i| i.stable_dt).min(0.25) * self.rate;
+        while self.play_clock >= 1.0 {
+            self.play_clock -= 1.0;
+            if self.version < self.max {
+                self.version += 1;
+            } else if self.looping {
+                self.version = self.min;
+            } else {
+                self.playing = false;
+                break;
+            }
+        }
+        ctx.request_repaint();
+    }
+
+    /// Step, jump to the first and the last version, and play or pause, with the keys of `Settings::keys`,
+    /// ←/→, Home/End and space by default.
+    fn shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let keys = self.settings.keys.clone();
+        let pressed = |i: &egui::InputState, name: &str| {
+            egui::Key::from_name(name).is_some_and(|key| i.key_pressed(key))
+        };
+        ctx.input(|i| {
+            if pressed(i, &keys.previous) {
+                self.step(-1);
+            }
+            if pressed(i, &keys.next) {
+                self.step(1);
+            }
+            if pressed(i, &keys.first) {
+                self.version = self.min;
+            }
+            if pressed(i, &keys.last) {
+                self.version = self.max;
+            }
+            if pressed(i, &keys.play) {
+                self.playing = !self.playing;
+            }
+        });
+    }
+
+    /// The settings the GUI was opened with, updated with the coloring, the overlays and the viewport on display.
+    fn view_settings(&self) -> Settings {
+        let mut res = self.settings.clone();
+        res.color.update(&self.x.config.color);
+        if let Some(preset) = self.preset {
+            res.color.preset = Some(preset.to_owned());
+        }
+        res.layers.overlaps = self.show_overlaps;
+        res.layers.density = self.show_density;
+        res.layers.density_bins = [self.density_bins.0, self.density_bins.1];
+        res.style = self.x.config.style;
+        if let Some(b) = self.bounds {
+            res.viewport = Some([b.ll.x, b.ll.y, b.ur.x, b.ur.y]);
+        }
+        res
+    }
+
+    fn save_settings(&mut self) {
+        let settings = self.view_settings();
+        self.settings_status = match settings.save(&self.settings_path) {
+            Ok(()) => format!("saved to {}", self.settings_path.display()),
+            Err(e) => e,
+        };
+        self.settings = settings;
+    }
+
+    /// Slider, step buttons and playback controls.
+    fn timeline(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui
+                .button("|<")
+                .on_hover_text(format!("first version ({})", self.settings.keys.first))
+                .clicked()
+            {
+                self.version = self.min;
+            }
+            if ui
+                .button("<")
+                .on_hover_text(format!(
+                    "previous version ({})",
+                    self.settings.keys.previous
+                ))
+                .clicked()
+            {
+                self.step(-1);
+            }
+            let play = if self.playing { "pause" } else { "play" };
+            if ui
+                .button(play)
+                .on_hover_text(&self.settings.keys.play)
+                .clicked()
+            {
+                self.playing = !self.playing;
+                if self.playing && self.version == self.max {
+                    self.version = self.min;
+                }
+            }
+            if ui
+                .button(">")
+                .on_hover_text(format!("next version ({})", self.settings.keys.next))
+                .clicked()
+            {
+                self.step(1);
+            }
+            if ui
+                .button(">|")
+                .on_hover_text(format!("last version ({})", self.settings.keys.last))
+                .clicked()
+            {
+                self.version = self.max;
+            }
+            ui.add(egui::Slider::new(&mut self.version, self.min..=self.max).text("version"));
+            ui.add(
+                egui::DragValue::new(&mut self.rate)
+                    .clamp_range(0.1..=self.settings.limits.max_rate)
+                    .speed(0.1)
+                    .suffix(" commits/s"),
+            );
+            ui.checkbox(&mut self.looping, "loop");
+            let next = self
+                .x
+                .next_change(&self.selected, VersionId(self.version as u32));
+            let jump = ui
+                .add_enabled(next.is_some(), egui::Button::new("next change"))
+                .on_hover_text("next version changing a selected shape");
+            if let (true, Some(next)) = (jump.clicked(), next) {
+                self.version = next.0 as usize;
+            }
+        });
+    }
 }
 
 impl eframe::App for ToPlot {
     fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
+        if self.show_stats {
+            let stats = self.stats.get_or_insert_with(|| self.x.stats());
+            egui::SidePanel::right("stats")
+                .default_width(320.0)
+                .show(ctx, |ui| stats_panel(ui, stats, self.version));
+        }
+        let clear = egui::Key::from_name(&self.settings.keys.clear_selection);
+        if clear.is_some_and(|key| ctx.input(|i| i.key_pressed(key))) {
+            self.selected.clear();
+        }
+        if !self.datasets.is_empty() {
+            egui::SidePanel::left("datasets")
+                .default_width(200.0)
+                .show(ctx, |ui| {
+                    datasets_panel(ui, &self.label, &mut self.show_main, &mut self.datasets)
+                });
+        }
+        if !self.selected.is_empty() {
+            egui::SidePanel::left("selection")
+                .default_width(240.0)
+                .show(ctx, |ui| {
+                    let version = VersionId(self.version as u32);
+                    selection_panel(ui, &self.x, version, &mut self.selected)
+                });
+        }
+        self.receive(ctx);
+        self.max = self.x.version.0 as usize;
+        self.shortcuts(ctx);
+        self.play(ctx);
         egui::CentralPanel::default().show(ctx, |ui| {
-            let mut v = self.version;
+            self.timeline(ui);
+            if let Some(ingest) = &self.incoming {
+                ingest_status(ui, ingest, &self.applied);
+            }
+            let v = self.version;
+
+            let find = ui
+                .horizontal(|ui| {
+                    let edit = ui.add(
+                        egui::TextEdit::singleline(&mut self.search).hint_text("find by name"),
+                    );
+                    ui.checkbox(&mut self.search_regex, "regex");
+                    let enter = edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                    let find = ui.button("find").clicked() || enter;
+                    ui.label(&self.search_status);
+                    find
+                })
+                .inner;
+
             ui.horizontal(|ui| {
-                ui.add(egui::Slider::new(&mut v, 0..=600_000).text(" version"));
+                ui.checkbox(&mut self.show_overlaps, "highlight overlaps");
+                ui.checkbox(&mut self.show_stats, "statistics");
+                ui.checkbox(&mut self.show_displacement, "displacement from");
+                ui.add(
+                    egui::DragValue::new(&mut self.displacement_from)
+                        .clamp_range(self.min..=self.max),
+                );
+                ui.checkbox(&mut self.show_density, "density map");
+                let bins = 1..=self.settings.limits.max_density_bins.max(1);
+                ui.add(
+                    egui::DragValue::new(&mut self.density_bins.0)
+                        .clamp_range(bins.clone())
+                        .prefix("x bins: "),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut self.density_bins.1)
+                        .clamp_range(bins)
+                        .prefix("y bins: "),
+                );
+                ui.menu_button("add pane", |ui| {
+                    let mut picked = None;
+                    if ui.button("this db").clicked() {
+                        picked = Some(Pane::new(None, self.version));
+                    }
+                    for (idx, dataset) in self.datasets.iter().enumerate() {
+                        if ui.button(&dataset.label).clicked() {
+                            let version = dataset.db.version.0 as usize;
+                            picked = Some(Pane::new(Some(idx), version));
+                        }
+                    }
+                    if let Some(pane) = picked {
+                        self.panes.push(pane);
+                        ui.close_menu();
+                    }
+                });
+                if ui
+                    .button("save view")
+                    .on_hover_text(format!(
+                        "save the coloring, overlays and viewport to {}",
+                        self.settings_path.display()
+                    ))
+                    .clicked()
+                {
+                    self.save_settings();
+                }
+                ui.label(&self.settings_status);
             });
 
-            ui.horizontal(|ui| {
-                ui.add(egui::Checkbox::new(&mut false, "hi"));
-                ui.add(egui::widgets::Spinner::new());
-                ui.add(egui::widgets::Label::new("hihi"));
+            let changed = ui
+                .horizontal(|ui| {
+                    let categorical =
+                        categorical_controls(ui, &mut self.x, &mut self.stashed_color);
+                    categorical | gradient_controls(ui, &mut self.x, &mut self.preset)
+                })
+                .inner;
+            if changed {
+                self.scene = None;
+                for pane in self.panes.iter_mut() {
+                    pane.scene = None;
+                }
+            }
+            let key = SceneKey {
+                version: VersionId(v as u32),
+                head: self.x.version,
+                overlaps: self.show_overlaps,
+                density: self.show_density.then_some(self.density_bins),
+                displacement_from: self
+                    .show_displacement
+                    .then_some(VersionId(self.displacement_from.min(self.max) as u32)),
+                outline: None,
+            };
+            self.refresh_scene(key);
+            let laid_over: Vec<_> = self
+                .datasets
+                .iter_mut()
+                .filter(|d| d.visible)
+                .map(|d| d.shapes(key))
+                .collect();
+            let scene = self.scene.as_ref().unwrap();
+            let sliced = &scene.sliced;
+
+            if find {
+                let matcher = if self.search_regex {
+                    Matcher::regex(&self.search)
+                } else {
+                    Matcher::glob(&self.search)
+                };
+                match matcher {
+                    Ok(matcher) => {
+                        let found = sliced.find(&matcher);
+                        self.search_status = format!("{} found", found.len());
+                        self.zoom_to = sliced.bounds(&found);
+                        self.selected = found.into_iter().collect();
+                    }
+                    Err(e) => self.search_status = e.to_string(),
+                }
+            }
+
+            let global = self.values.as_ref().map_or(&[][..], |(_, v)| v);
+            if let Some(map) = self.x.config.color.color_map(global, &scene.values) {
+                colorbar(ui, &map);
+            }
+            if let (ColorType::Categorical { palette, .. }, Some((_, categories))) =
+                (&self.x.config.color, &self.categories)
+            {
+                category_legend(ui, palette, categories, self.settings.limits.legend_entries);
+            }
+
+            let displacement = (!scene.arrows.is_empty()).then(|| {
+                Vector::arrows(scene.arrows.iter().copied())
+                    .name("displacement")
+                    .color(Color32::BLACK)
             });
 
-            let polygons: Vec<_> = self
-                .x
-                .slice(crate::db::version_controller::VersionId(v as u32))
-                .rects
+            let accent = ui.visuals().selection.bg_fill;
+            let selection_width = self.x.config.style.selection_width;
+            let selected: Vec<_> = self
+                .selected
                 .iter()
-                .map(|(name, geo)| {
-                    let ll = egui_plot::PlotPoint::new(geo.geo.ll.x, geo.geo.ll.y);
-                    let lr = egui_plot::PlotPoint::new(geo.geo.ur.x, geo.geo.ll.y);
-                    let ur = egui_plot::PlotPoint::new(geo.geo.ur.x, geo.geo.ur.y);
-                    let ul = egui_plot::PlotPoint::new(geo.geo.ll.x, geo.geo.ur.y);
-                    let pps = PlotPoints::Owned(vec![ll, lr, ur, ul]);
-                    let mut res = Polygon::new(pps).name(String::from_utf8_lossy(name.as_ref()));
-                    if let Some(c) = geo.color {
-                        // res = res.fill_color(Color32::from_rgb(c.r / 4 * 3,c.g / 4 * 3,c.b / 4 * 3));
-                        res = res
-                            .stroke(Stroke::new(0.0, Color32::from_rgb(c.r, c.g, c.b)))
-                            .fill_color(Color32::from_rgb(c.r, c.g, c.b));
-                        res = res.highlight(true);
-                    };
-                    res
+                .filter_map(|name| {
+                    let rect = sliced.rects.get(name).map(|r| rect_points(&r.geo));
+                    rect.map(|pps| Polygon::new(pps).fill_color(Color32::TRANSPARENT))
+                })
+                .map(|p| {
+                    p.name("selected")
+                        .stroke(Stroke::new(selection_width, accent))
                 })
                 .collect();
-
-            let lines: Vec<_> = self
-                .x
-                .slice(crate::db::version_controller::VersionId(v as u32))
-                .lines
+            let selected_lines: Vec<_> = self
+                .selected
                 .iter()
-                .map(|(name, geo)| {
-                    let from = egui_plot::PlotPoint::new(geo.geo.ll.x, geo.geo.ll.y);
-                    let to = egui_plot::PlotPoint::new(geo.geo.ur.x, geo.geo.ur.y);
-                    let pps = PlotPoints::Owned(vec![from, to]);
-                    let mut res =
-                        egui_plot::Line::new(pps).name(String::from_utf8_lossy(name.as_ref()));
-                    if let Some(c) = geo.color {
-                        res = res.stroke(Stroke::new(0.0, Color32::from_rgb(c.r, c.g, c.b)));
-                        res = res.highlight(true);
-                    };
-                    res
+                .filter_map(|name| sliced.lines.get(name))
+                .map(|l| {
+                    let width = l.style.width.unwrap_or(1.0) + 2.0;
+                    egui_plot::Line::new(PlotPoints::from(l.geo))
+                        .name("selected")
+                        .stroke(Stroke::new(width, accent))
                 })
                 .collect();
 
-            Plot::new("plot")
-                .allow_boxed_zoom(true)
-                .show(ui, |plot_ui| {
-                    for polygon in polygons {
-                        plot_ui.polygon(polygon);
+            let mut panes = std::mem::take(&mut self.panes);
+            ui.columns(1 + panes.len(), |columns| {
+                let (ui, others) = columns.split_first_mut().unwrap();
+                let modifiers = ctx.input(|i| i.modifiers);
+                let zoom_to = self.zoom_to.take();
+                let restore = self.restore.take();
+                let band_start = self.band_start;
+                let plot = linked(Plot::new("plot"))
+                    .allow_boxed_zoom(true)
+                    .allow_drag(!modifiers.shift)
+                    .show(ui, |plot_ui| {
+                        if let Some(area) = zoom_to {
+                            plot_ui.set_plot_bounds(zoom_bounds(&area));
+                        }
+                        if let Some(area) = restore {
+                            plot_ui.set_plot_bounds(PlotBounds::from_min_max(
+                                [area.ll.x, area.ll.y],
+                                [area.ur.x, area.ur.y],
+                            ));
+                        }
+                        if self.show_main {
+                            plot_ui.add(BatchItem(scene.shapes.clone()));
+                        }
+                        for shapes in laid_over {
+                            plot_ui.add(BatchItem(shapes));
+                        }
+                        plot_ui.add(BatchItem(scene.overlays.clone()));
+                        if let Some(arrows) = displacement {
+                            plot_ui.arrows(arrows);
+                        }
+                        for polygon in selected {
+                            plot_ui.polygon(polygon);
+                        }
+                        for line in selected_lines {
+                            plot_ui.line(line);
+                        }
+                        let pointer = plot_ui.pointer_coordinate();
+                        if let (Some(start), Some(end)) = (band_start, pointer) {
+                            let area = band(start, end);
+                            plot_ui.polygon(
+                                Polygon::new(rect_points(&area))
+                                    .name("selection")
+                                    .stroke(Stroke::new(1.0, accent))
+                                    .fill_color(accent.gamma_multiply(0.2)),
+                            );
+                        }
+                        // Pick shapes within a few pixels of the pointer.
+                        let tolerance = 4.0 * plot_ui.transform().dvalue_dpos()[0].abs();
+                        (pointer, tolerance)
+                    });
+
+                // Click to select, with ctrl to add or remove, shift-drag a rubber band to select an area.
+                let (pointer, tolerance) = plot.inner;
+                let shown = plot.transform.bounds();
+                self.bounds = Some(Rect::new(
+                    Point::new(shown.min()[0], shown.min()[1]),
+                    Point::new(shown.max()[0], shown.max()[1]),
+                ));
+                let response = &plot.response;
+                let hits = match pointer {
+                    Some(p) if response.hovered() => sliced.hit(&Point::new(p.x, p.y), tolerance),
+                    _ => vec![],
+                };
+                if let (Some(name), None) = (hits.first(), self.band_start) {
+                    egui::show_tooltip_at_pointer(ctx, egui::Id::new("hovered"), |ui| {
+                        shape_info(ui, &self.x, VersionId(v as u32), name)
+                    });
+                }
+                if response.clicked() {
+                    if !modifiers.command {
+                        self.selected.clear();
                     }
-                    for line in lines {
-                        plot_ui.line(line);
+                    if let Some(name) = hits.first() {
+                        if !self.selected.remove(name) {
+                            self.selected.insert(name.clone());
+                        }
                     }
+                }
+                if modifiers.shift && response.drag_started_by(egui::PointerButton::Primary) {
+                    self.band_start = pointer;
+                }
+                if response.drag_stopped() {
+                    if let (Some(start), Some(end)) = (self.band_start.take(), pointer) {
+                        let area = band(start, end);
+                        if !modifiers.command {
+                            self.selected.clear();
+                        }
+                        self.selected.extend(sliced.within(&area));
+                    }
+                }
+                let datasets = &self.datasets;
+                let main = &self.x;
+                let mut idx = 0;
+                let mut others = others.iter_mut();
+                panes.retain_mut(|pane| {
+                    let ui = others.next().unwrap();
+                    let (db, label) = match pane.dataset {
+                        Some(d) => (&datasets[d].db, datasets[d].label.as_str()),
+                        None => (main, "this db"),
+                    };
+                    idx += 1;
+                    pane.show(ui, idx, db, label, key)
                 });
-
-            self.version = v;
+            });
+            self.panes = panes;
         });
     }
 }
@@ -83,14 +1132,70 @@ impl eframe::App for ToPlot {
 #[derive(Default)]
 pub struct Config {
     pub color: ColorType,
+    pub policy: ConflictPolicy,
+    pub validation: Validation,
+    pub style: LayerStyle,
 }
 
-pub fn run_gui(db: Db) {
+/// Open the GUI on `db`. The `datasets` not laid over it open in panes of their own.
+/// Commits coming from `incoming` are added to `db` while the GUI runs.
+/// The view starts as given by `settings`, and "save view" writes it to `settings_path`.
+pub fn run_gui(
+    label: String,
+    db: Db,
+    datasets: Vec<Dataset>,
+    incoming: Option<Ingest>,
+    settings: Settings,
+    settings_path: PathBuf,
+) {
     let gui = ToPlot {
-        x: db,
         version: 0,
-        min: 0,
-        max: 1000,
+        min: VersionId::GENESIS.0 as usize,
+        max: db.version.0 as usize,
+        playing: false,
+        rate: 10.0,
+        looping: false,
+        play_clock: 0.0,
+        show_overlaps: settings.layers.overlaps,
+        show_density: settings.layers.density,
+        density_bins: (
+            settings.layers.density_bins[0].max(1),
+            settings.layers.density_bins[1].max(1),
+        ),
+        show_stats: false,
+        stats: None,
+        show_displacement: false,
+        displacement_from: 0,
+        values: None,
+        preset: PRESETS
+            .into_iter()
+            .find(|p| settings.color.preset.as_deref() == Some(*p)),
+        stashed_color: None,
+        categories: None,
+        selected: BTreeSet::new(),
+        search: String::new(),
+        search_regex: false,
+        search_status: String::new(),
+        zoom_to: None,
+        band_start: None,
+        scene: None,
+        label,
+        show_main: true,
+        incoming,
+        applied: Applied::default(),
+        panes: datasets
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| !d.visible)
+            .map(|(idx, d)| Pane::new(Some(idx), d.db.version.0 as usize))
+            .collect(),
+        datasets,
+        x: db,
+        restore: settings.viewport(),
+        settings,
+        settings_path,
+        settings_status: String::new(),
+        bounds: None,
     };
     eframe::run_native(
         "geo_git",
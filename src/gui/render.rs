@@ -0,0 +1,626 @@
+use std::{fmt::Write as _, io, path::Path};
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use eframe::egui::{Color32, FontDefinitions};
+
+use super::scene::{gradient_channel, Batch, Pen};
+use crate::{
+    db::{r#impl::Db, version_controller::VersionId},
+    geo::{color::Color, line::Line, point::Point, rect::Rect},
+};
+
+/// Which layers of a version are drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Layers {
+    pub rects: bool,
+    pub lines: bool,
+    pub overlaps: bool,
+    /// Number of density bins along x and y, if the density map is drawn.
+    pub density: Option<(usize, usize)>,
+}
+
+impl Layers {
+    /// Drop the rects or the lines of `batch`, if they are not drawn.
+    fn filter(&self, batch: &mut Batch) {
+        if !self.rects {
+            batch.fills.clear();
+            batch.outlines.clear();
+        }
+        if !self.lines {
+            batch.lines.clear();
+        }
+    }
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Self {
+            rects: true,
+            lines: true,
+            overlaps: false,
+            density: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RenderOptions {
+    pub width: u32,
+    pub height: u32,
+    /// Area of the layout to show, the bounding box of the shapes if `None`.
+    pub viewport: Option<Rect>,
+    pub background: Color,
+    pub layers: Layers,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 1024,
+            viewport: None,
+            background: Color::rgb(255, 255, 255),
+            layers: Layers::default(),
+        }
+    }
+}
+
+/// What the coloring of a version takes from the whole db, the same for every version:
+/// every value of the gradient channel, sorted, and every category of the categorical attribute.
+pub struct DbColors {
+    pub global: Vec<f32>,
+    pub categories: Vec<String>,
+}
+
+impl DbColors {
+    pub fn new(db: &Db) -> Self {
+        let global = gradient_channel(db)
+            .map(|c| db.values(c))
+            .unwrap_or_default();
+        let categories = db
+            .config
+            .color
+            .attribute()
+            .map(|a| db.categories(a))
+            .unwrap_or_default();
+        Self { global, categories }
+    }
+}
+
+/// `viewport`, or a padded square around its center if it has no width or no height to scale,
+/// such as the bounding box of a single point. The square takes the other side of the viewport,
+/// or a unit side if both are empty.
+fn scalable(viewport: Rect) -> Rect {
+    let (width, height) = (viewport.width(), viewport.height());
+    if width > 0.0 && height > 0.0 && width.is_finite() && height.is_finite() {
+        return viewport;
+    }
+    let side = width.max(height);
+    let side = if side > 0.0 && side.is_finite() {
+        side
+    } else {
+        1.0
+    };
+    let center = viewport.center();
+    let center = if center.x.is_finite() && center.y.is_finite() {
+        center
+    } else {
+        Point::new(0.0, 0.0)
+    };
+    let half = Point::new(side / 2.0, side / 2.0);
+    Rect::new(
+        Point::new(center.x - half.x, center.y - half.y),
+        Point::new(center.x + half.x, center.y + half.y),
+    )
+    .expand(0.02 * side)
+}
+
+/// Batches of shapes laid out on an image, to be rasterized to PNG or written as SVG without a window.
+/// The layout keeps its aspect ratio and is centered in the image.
+pub struct Picture {
+    pub width: u32,
+    pub height: u32,
+    pub background: Color,
+    pub viewport: Rect,
+    /// Drawn in order, the later on top.
+    pub batches: Vec<Batch>,
+    /// Lines of text drawn in the top left corner, over the shapes.
+    pub caption: Vec<String>,
+}
+
+impl Picture {
+    pub fn new(batches: Vec<Batch>, opts: &RenderOptions) -> Self {
+        let viewport = opts.viewport.unwrap_or_else(|| {
+            let bbox = Rect::bounding(batches.iter().filter_map(|b| b.bbox));
+            let bbox = bbox.unwrap_or(Rect::new(Point::new(0.0, 0.0), Point::new(1.0, 1.0)));
+            bbox.expand(0.02 * bbox.width().max(bbox.height()))
+        });
+        Self {
+            width: opts.width,
+            height: opts.height,
+            background: opts.background,
+            viewport: scalable(viewport),
+            batches,
+            caption: vec![],
+        }
+    }
+
+    /// Version `version` of `db`, colored as in the GUI.
+    pub fn version(db: &Db, version: VersionId, opts: &RenderOptions) -> Self {
+        Self::version_colored(db, &DbColors::new(db), version, opts)
+    }
+
+    /// `Picture::version`, with the coloring of the whole `db` computed beforehand.
+    pub fn version_colored(
+        db: &Db,
+        colors: &DbColors,
+        version: VersionId,
+        opts: &RenderOptions,
+    ) -> Self {
+        let sliced = db.slice(version);
+        let values = gradient_channel(db)
+            .map(|c| sliced.values(c))
+            .unwrap_or_default();
+        let mut shapes = Batch::colored(db, &sliced, &colors.global, &values, &colors.categories);
+        opts.layers.filter(&mut shapes);
+        let overlays = Batch::overlays(db, &sliced, opts.layers.density, opts.layers.overlaps);
+        Self::new(vec![shapes, overlays], opts)
+    }
+
+    /// The changes of `db` from version `from` to version `to`, see `Batch::diff`,
+    /// with the layers of `opts` as for `Picture::version` of `to`.
+    pub fn diff(db: &Db, from: VersionId, to: VersionId, opts: &RenderOptions) -> Self {
+        let after = db.slice(to);
+        let mut diff = Batch::diff(&db.slice(from), &after);
+        opts.layers.filter(&mut diff);
+        let overlays = Batch::overlays(db, &after, opts.layers.density, opts.layers.overlaps);
+        Self::new(vec![diff, overlays], opts)
+    }
+
+    /// Size of the caption font in pixels, following the size of the image.
+    fn font_size(&self) -> f32 {
+        (self.height as f32 / 48.0).clamp(12.0, 32.0)
+    }
+
+    /// Pixels per unit of the layout, and the offset of the viewport in the image.
+    fn scale(&self) -> (f64, f64, f64) {
+        let v = &self.viewport;
+        let s = (self.width as f64 / v.width()).min(self.height as f64 / v.height());
+        let ox = (self.width as f64 - v.width() * s) / 2.0;
+        let oy = (self.height as f64 - v.height() * s) / 2.0;
+        (s, ox, oy)
+    }
+
+    /// Image coordinates of `p`, with y pointing down.
+    fn to_px(&self, p: &Point) -> Point {
+        let (s, ox, oy) = self.scale();
+        Point::new(
+            ox + (p.x - self.viewport.ll.x) * s,
+            self.height as f64 - oy - (p.y - self.viewport.ll.y) * s,
+        )
+    }
+
+    fn rect_px(&self, r: &Rect) -> Rect {
+        let (a, b) = (self.to_px(&r.ll), self.to_px(&r.ur));
+        Rect::new(
+            Point::new(a.x.min(b.x), a.y.min(b.y)),
+            Point::new(a.x.max(b.x), a.y.max(b.y)),
+        )
+    }
+
+    /// Rasterize to RGBA8 pixels, row by row from the top.
+    pub fn raster(&self) -> Vec<u8> {
+        let mut canvas = Canvas {
+            width: self.width as usize,
+            height: self.height as usize,
+            rgba: [
+                self.background.r,
+                self.background.g,
+                self.background.b,
+                self.background.a,
+            ]
+            .repeat(self.width as usize * self.height as usize),
+        };
+        for batch in self.batches.iter() {
+            for (rect, color) in batch.fills.iter() {
+                if rect.intersects(&self.viewport) {
+                    canvas.fill(&self.rect_px(rect), *color);
+                }
+            }
+            for (rect, pen) in batch.outlines.iter() {
+                if !rect.intersects(&self.viewport) {
+                    continue;
+                }
+                let r = self.rect_px(rect);
+                let corners = [
+                    r.ll,
+                    Point::new(r.ur.x, r.ll.y),
+                    r.ur,
+                    Point::new(r.ll.x, r.ur.y),
+                ];
+                for i in 0..4 {
+                    canvas.stroke(&Line::new(corners[i], corners[(i + 1) % 4]), pen);
+                }
+            }
+            for (line, pen) in batch.lines.iter() {
+                if line.bbox().intersects(&self.viewport) {
+                    let px = Line::new(self.to_px(&line.ll), self.to_px(&line.ur));
+                    canvas.stroke(&px, pen);
+                }
+            }
+        }
+        if !self.caption.is_empty() {
+            canvas.caption(&self.caption, self.font_size());
+        }
+        canvas.rgba
+    }
+
+    pub fn write_png(&self, out: impl io::Write) -> Result<(), png::EncodingError> {
+        write_png(out, self.width, self.height, &self.raster())
+    }
+
+    pub fn svg(&self) -> String {
+        let mut res = String::new();
+        // The color and the opacity of a paint, for `fill` and `fill-opacity` or `stroke` and `stroke-opacity`.
+        let paint = |c: &Color32| {
+            let [r, g, b, a] = c.to_srgba_unmultiplied();
+            (format!("rgb({},{},{})", r, g, b), a as f32 / 255.0)
+        };
+        let fill = |c: &Color32| {
+            let (color, opacity) = paint(c);
+            format!("fill=\"{}\" fill-opacity=\"{:.3}\"", color, opacity)
+        };
+        let stroke = |pen: &Pen| {
+            let (color, opacity) = paint(&pen.stroke.color);
+            let mut res = format!(
+                "stroke=\"{}\" stroke-opacity=\"{:.3}\" stroke-width=\"{}\"",
+                color, opacity, pen.stroke.width
+            );
+            if let Some(dash) = pen.dash {
+                write!(res, " stroke-dasharray=\"{}\"", dash).unwrap();
+            }
+            res
+        };
+        writeln!(
+            res,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">",
+            w = self.width,
+            h = self.height
+        )
+        .unwrap();
+        let bg = self.background;
+        writeln!(
+            res,
+            "<rect width=\"100%\" height=\"100%\" {}/>",
+            fill(&Color32::from_rgba_unmultiplied(bg.r, bg.g, bg.b, bg.a))
+        )
+        .unwrap();
+        for batch in self.batches.iter() {
+            for (rect, color) in batch.fills.iter() {
+                if rect.intersects(&self.viewport) {
+                    let r = self.rect_px(rect);
+                    writeln!(
+                        res,
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {}/>",
+                        r.ll.x,
+                        r.ll.y,
+                        r.width(),
+                        r.height(),
+                        fill(color)
+                    )
+                    .unwrap();
+                }
+            }
+            for (rect, pen) in batch.outlines.iter() {
+                if rect.intersects(&self.viewport) {
+                    let r = self.rect_px(rect);
+                    writeln!(
+                        res,
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" {}/>",
+                        r.ll.x,
+                        r.ll.y,
+                        r.width(),
+                        r.height(),
+                        stroke(pen)
+                    )
+                    .unwrap();
+                }
+            }
+            for (line, pen) in batch.lines.iter() {
+                if line.bbox().intersects(&self.viewport) {
+                    let (a, b) = (self.to_px(&line.ll), self.to_px(&line.ur));
+                    writeln!(
+                        res,
+                        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" {}/>",
+                        a.x,
+                        a.y,
+                        b.x,
+                        b.y,
+                        stroke(pen)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        if !self.caption.is_empty() {
+            let size = self.font_size();
+            let longest = self.caption.iter().map(|l| l.chars().count()).max();
+            writeln!(
+                res,
+                "<rect x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"white\" opacity=\"0.8\"/>",
+                longest.unwrap_or(0) as f32 * size * 0.6 + 2.0 * CAPTION_PADDING,
+                self.caption.len() as f32 * size * LINE_HEIGHT + 2.0 * CAPTION_PADDING
+            )
+            .unwrap();
+            for (i, line) in self.caption.iter().enumerate() {
+                writeln!(
+                    res,
+                    "<text x=\"{}\" y=\"{}\" font-family=\"monospace\" font-size=\"{}\">{}</text>",
+                    CAPTION_PADDING,
+                    CAPTION_PADDING + (i as f32 + 0.8) * size * LINE_HEIGHT,
+                    size,
+                    escape(line)
+                )
+                .unwrap();
+            }
+        }
+        res.push_str("</svg>\n");
+        res
+    }
+
+    /// Write to `path` as PNG or SVG, picked by its extension.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("png") => {
+                let file = io::BufWriter::new(std::fs::File::create(path)?);
+                self.write_png(file).map_err(io::Error::other)
+            }
+            Some("svg") => std::fs::write(path, self.svg()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot tell the format of `{}`", path.display()),
+            )),
+        }
+    }
+}
+
+/// Encode RGBA8 pixels as a PNG image.
+pub(crate) fn write_png(
+    out: impl io::Write,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)
+}
+
+/// Space between the caption and the border of its box, in pixels.
+const CAPTION_PADDING: f32 = 4.0;
+/// Height of a caption line, relative to the font size.
+const LINE_HEIGHT: f32 = 1.2;
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// RGBA8 pixels the shapes are blended onto.
+struct Canvas {
+    width: usize,
+    height: usize,
+    rgba: Vec<u8>,
+}
+
+impl Canvas {
+    fn blend(&mut self, x: usize, y: usize, color: Color32) {
+        let [r, g, b, a] = color.to_srgba_unmultiplied();
+        if a == 0 {
+            return;
+        }
+        let alpha = a as f32 / 255.0;
+        let px = &mut self.rgba[(y * self.width + x) * 4..][..4];
+        for (dst, src) in px.iter_mut().zip([r, g, b]) {
+            *dst = (src as f32 * alpha + *dst as f32 * (1.0 - alpha)).round() as u8;
+        }
+        px[3] = (a as f32 + px[3] as f32 * (1.0 - alpha)).round() as u8;
+    }
+
+    /// Range of the pixels whose centers lie in `lo..=hi`, at least the pixel of the middle,
+    /// so that shapes thinner than a pixel still show.
+    fn span(lo: f64, hi: f64, len: usize) -> std::ops::Range<usize> {
+        let (mut from, mut to) = ((lo - 0.5).ceil(), (hi - 0.5).floor() + 1.0);
+        if from >= to {
+            from = ((lo + hi) / 2.0).floor();
+            to = from + 1.0;
+        }
+        from.clamp(0.0, len as f64) as usize..to.clamp(0.0, len as f64) as usize
+    }
+
+    fn fill(&mut self, r: &Rect, color: Color32) {
+        for y in Self::span(r.ll.y, r.ur.y, self.height) {
+            for x in Self::span(r.ll.x, r.ur.x, self.width) {
+                self.blend(x, y, color);
+            }
+        }
+    }
+
+    /// Write `lines` in the top left corner, black on a translucent white box,
+    /// with the monospace font the GUI uses.
+    fn caption(&mut self, lines: &[String], size: f32) {
+        let fonts = FontDefinitions::default();
+        let Some(font) = fonts
+            .font_data
+            .get("Hack")
+            .and_then(|data| FontRef::try_from_slice(&data.font).ok())
+        else {
+            return;
+        };
+        let scale = PxScale::from(size);
+        let scaled = font.as_scaled(scale);
+        let advance = |line: &str| -> f32 {
+            line.chars()
+                .map(|c| scaled.h_advance(scaled.glyph_id(c)))
+                .sum()
+        };
+        let width = lines.iter().map(|l| advance(l)).fold(0.0, f32::max);
+        let height = lines.len() as f32 * size * LINE_HEIGHT;
+        let pad = CAPTION_PADDING as f64;
+        let area = Rect::new(
+            Point::new(0.0, 0.0),
+            Point::new(width as f64 + 2.0 * pad, height as f64 + 2.0 * pad),
+        );
+        self.fill(&area, Color32::from_white_alpha(204));
+        for (i, line) in lines.iter().enumerate() {
+            let mut caret = ab_glyph::point(
+                CAPTION_PADDING,
+                CAPTION_PADDING + i as f32 * size * LINE_HEIGHT + scaled.ascent(),
+            );
+            for c in line.chars() {
+                let id = scaled.glyph_id(c);
+                let glyph = id.with_scale_and_position(scale, caret);
+                caret.x += scaled.h_advance(id);
+                let Some(outline) = font.outline_glyph(glyph) else {
+                    continue;
+                };
+                let bounds = outline.px_bounds();
+                outline.draw(|gx, gy, coverage| {
+                    let x = bounds.min.x as i64 + gx as i64;
+                    let y = bounds.min.y as i64 + gy as i64;
+                    if (0..self.width as i64).contains(&x) && (0..self.height as i64).contains(&y) {
+                        let alpha = (coverage.min(1.0) * 255.0).round() as u8;
+                        self.blend(x as usize, y as usize, Color32::from_black_alpha(alpha));
+                    }
+                });
+            }
+        }
+    }
+
+    /// Draw the pixels within half the stroke width of `line`, skipping the gaps of a dashed pen.
+    fn stroke(&mut self, line: &Line, pen: &Pen) {
+        let half = (pen.stroke.width as f64 / 2.0).max(0.5);
+        if pen.stroke.width <= 0.0 {
+            return;
+        }
+        let area = line.bbox().expand(half);
+        let len = line.length();
+        for y in Self::span(area.ll.y, area.ur.y, self.height) {
+            for x in Self::span(area.ll.x, area.ur.x, self.width) {
+                let p = Point::new(x as f64 + 0.5, y as f64 + 0.5);
+                if line.distance(&p) > half {
+                    continue;
+                }
+                if let (Some(dash), true) = (pen.dash, len > 0.0) {
+                    let along = ((p.x - line.ll.x) * (line.ur.x - line.ll.x)
+                        + (p.y - line.ll.y) * (line.ur.y - line.ll.y))
+                        / len;
+                    if (along / dash as f64).floor() as i64 % 2 == 1 {
+                        continue;
+                    }
+                }
+                self.blend(x, y, pen.stroke.color);
+            }
+        }
+    }
+}
+
+#[test]
+fn render_version() {
+    use crate::{gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 1 1 #ff0000 ;\nADDRECT B 1 0 2 1 #0000ff ;\nADDLINE L 0 2 2 2 #00ff00 ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let opts = RenderOptions {
+        width: 20,
+        height: 20,
+        viewport: Some(Rect::new(Point::new(0.0, 0.0), Point::new(2.0, 2.0))),
+        ..Default::default()
+    };
+    let picture = Picture::version(&db, VersionId(1), &opts);
+    let rgba = picture.raster();
+    let at = |x: usize, y: usize| &rgba[(y * 20 + x) * 4..][..4];
+    // y points down in the image, the rects fill its lower half.
+    assert_eq!(at(5, 15), [255, 0, 0, 255]);
+    assert_eq!(at(15, 15), [0, 0, 255, 255]);
+    assert_eq!(at(10, 5), [255, 255, 255, 255]);
+    assert_eq!(at(10, 0), [0, 255, 0, 255]);
+
+    let svg = picture.svg();
+    assert_eq!(svg.matches("<rect").count(), 3);
+    assert_eq!(svg.matches("<line").count(), 1);
+    assert!(svg.contains("fill=\"rgb(255,0,0)\" fill-opacity=\"1.000\""));
+    assert!(svg.contains("stroke=\"rgb(0,255,0)\" stroke-opacity=\"1.000\""));
+    assert!(!svg.contains(" opacity=\"1.000\""));
+
+    let without_rects = RenderOptions {
+        layers: Layers {
+            rects: false,
+            ..Default::default()
+        },
+        ..opts
+    };
+    let rgba = Picture::version(&db, VersionId(1), &without_rects).raster();
+    assert_eq!(&rgba[(15 * 20 + 5) * 4..][..4], [255, 255, 255, 255]);
+
+    // The layers apply to a diff as well.
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 1 1 ;\nADDLINE L 0 2 2 2 ;\nCOMMIT\nADDRECT B 0.5 0 2 1 ;\n".into(),
+        Config::default(),
+    )
+    .unwrap();
+    let svg = Picture::diff(&db, VersionId(1), VersionId(2), &without_rects).svg();
+    assert_eq!(svg.matches("<rect").count(), 1);
+    assert_eq!(svg.matches("<line").count(), 1);
+    let overlaps = RenderOptions {
+        layers: Layers {
+            lines: false,
+            overlaps: true,
+            ..Default::default()
+        },
+        ..opts
+    };
+    let svg = Picture::diff(&db, VersionId(1), VersionId(2), &overlaps).svg();
+    assert_eq!(svg.matches("<rect").count(), 4);
+    assert_eq!(svg.matches("<line").count(), 0);
+
+    let mut png = vec![];
+    picture.write_png(&mut png).unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+}
+
+#[test]
+fn degenerate_viewport() {
+    use crate::{gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT P 1 1 1 1 #ff0000 ;\n".into(),
+        Config::default(),
+    )
+    .unwrap();
+    let opts = RenderOptions {
+        width: 21,
+        height: 21,
+        ..Default::default()
+    };
+    let picture = Picture::version(&db, VersionId(1), &opts);
+    assert!(picture.viewport.width() > 0.0 && picture.viewport.height() > 0.0);
+    assert_eq!(picture.viewport.center(), Point::new(1.0, 1.0));
+    let rgba = picture.raster();
+    assert_eq!(&rgba[(10 * 21 + 10) * 4..][..4], [255, 0, 0, 255]);
+
+    let flat = RenderOptions {
+        viewport: Some(Rect::new(Point::new(0.0, 0.0), Point::new(4.0, 0.0))),
+        ..opts
+    };
+    let picture = Picture::version(&db, VersionId(1), &flat);
+    assert_eq!(picture.viewport.height(), picture.viewport.width());
+    assert!(picture.svg().contains("<rect"));
+}
@@ -0,0 +1,511 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::RangeInclusive,
+    sync::Arc,
+};
+
+use bytes::Bytes;
+use eframe::egui::{self, epaint::Mesh, Color32, Pos2, Shape, Stroke};
+use egui_plot::{PlotBounds, PlotGeometry, PlotItem, PlotPoint, PlotTransform};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    analysis::diff::ChangeKind,
+    db::{
+        r#impl::{Db, SlicedDb, Values},
+        version_controller::VersionId,
+    },
+    geo::{color::Color, line::Line, point::Point, rect::Rect, style::Style, vector::Vector},
+};
+
+/// Screen size, in points, below which a rect is drawn as a single dot, without its outline.
+pub const LOD_SIZE: f32 = 2.0;
+
+/// Drawn for shapes without any color.
+const DEFAULT_COLOR: Color32 = Color32::GRAY;
+
+/// Colors of what the coloring of a db does not decide: shapes without any color, and the overlays.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayerStyle {
+    /// Drawn for shapes without any color, with a translucent fill for rects.
+    pub default_color: Color,
+    /// Fill of the regions where rects overlap.
+    pub overlap_color: Color,
+    /// Opacity of the density bins, from 0 to 255.
+    pub density_alpha: u8,
+    /// Width, in points, of the outline of the selected rects.
+    pub selection_width: f32,
+}
+
+impl Default for LayerStyle {
+    fn default() -> Self {
+        let [r, g, b, _] = DEFAULT_COLOR.to_array();
+        Self {
+            default_color: Color::rgb(r, g, b),
+            overlap_color: Color::rgb(255, 0, 0),
+            density_alpha: 160,
+            selection_width: 2.5,
+        }
+    }
+}
+
+pub(crate) fn color32(c: Color) -> Color32 {
+    Color32::from_rgba_unmultiplied(c.r, c.g, c.b, c.a)
+}
+
+/// The channel colored by the gradient of `db.config.color`, if it has any value.
+pub fn gradient_channel(db: &Db) -> Option<usize> {
+    db.config.color.channel().and_then(|c| db.channel(c))
+}
+
+/// How an outline or a line is stroked.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Pen {
+    pub stroke: Stroke,
+    /// Length of the dashes and of the gaps between them, solid if `None`.
+    pub dash: Option<f32>,
+}
+
+impl Pen {
+    fn new(width: f32, color: Color32, style: &Style) -> Self {
+        Self {
+            stroke: Stroke::new(width, color),
+            dash: style.dash.filter(|d| *d > 0.0),
+        }
+    }
+}
+
+/// Shapes ready to be painted in one go. Fills and solid strokes all go into a single mesh,
+/// with the color on the vertices, so the cost of a frame does not depend on the number of colors.
+#[derive(Default)]
+pub struct Batch {
+    pub fills: Vec<(Rect, Color32)>,
+    pub outlines: Vec<(Rect, Pen)>,
+    pub lines: Vec<(Line, Pen)>,
+    /// Bounds of the shapes, for the auto-bounds of the plot.
+    pub bbox: Option<Rect>,
+}
+
+impl Batch {
+    /// Batch the rects and lines of `sliced`, colored by `color_of` from their name, description,
+    /// raw color and values, or with `style.default_color` when it gives none.
+    pub fn of(
+        sliced: &SlicedDb,
+        style: &LayerStyle,
+        color_of: impl Fn(&[u8], Option<&Bytes>, Option<Color>, &Values) -> Option<Color>,
+    ) -> Self {
+        let default = color32(style.default_color);
+        let mut res = Self::default();
+        for (name, r) in sliced.rects.iter() {
+            let fill = color_of(name, r.desc.as_ref(), r.color, &r.values).map(color32);
+            let outline = r.style.is_outline();
+            let stroke = r.style.stroke.map(color32).or(fill);
+            match (fill, stroke) {
+                (None, None) => {
+                    if !outline {
+                        res.fills.push((r.geo, default.gamma_multiply(0.5)));
+                    }
+                    let width = r.style.width.unwrap_or(1.0);
+                    res.outlines
+                        .push((r.geo, Pen::new(width, default, &r.style)));
+                }
+                _ => {
+                    if let (Some(c), false) = (fill, outline) {
+                        res.fills.push((r.geo, c));
+                    }
+                    let width = r.style.width.unwrap_or(if outline { 1.0 } else { 0.0 });
+                    if let (Some(c), true) = (stroke, width > 0.0) {
+                        res.outlines.push((r.geo, Pen::new(width, c, &r.style)));
+                    }
+                }
+            }
+        }
+        for (name, l) in sliced.lines.iter() {
+            let color = color_of(name, l.desc.as_ref(), l.color, &l.values).map(color32);
+            let color = l.style.stroke.map(color32).or(color).unwrap_or(default);
+            let width = l.style.width.unwrap_or(1.0);
+            res.lines.push((l.geo, Pen::new(width, color, &l.style)));
+        }
+        let lines = sliced.lines.values().map(|l| l.geo.bbox());
+        res.bbox = Rect::bounding(sliced.bbox().into_iter().chain(lines));
+        res
+    }
+
+    /// Batch `sliced` colored by `db.config.color`, the way every view of the db colors it.
+    /// `global` holds every value of the gradient channel in the db and `version` those of `sliced`,
+    /// both sorted, and `categories` every category of the attribute of categorical coloring.
+    pub fn colored(
+        db: &Db,
+        sliced: &SlicedDb,
+        global: &[f32],
+        version: &[f32],
+        categories: &[String],
+    ) -> Self {
+        let channel = gradient_channel(db);
+        let attribute = db.config.color.attribute();
+        let color_map = db.config.color.color_map(global, version);
+        let category_map = db.config.color.category_map(categories);
+        let color_of = |name: &[u8],
+                        desc: Option<&Bytes>,
+                        color: Option<Color>,
+                        values: &Values| {
+            match (&category_map, attribute, &color_map) {
+                (Some(map), Some(attribute), _) => db
+                    .category(attribute, name, desc, values)
+                    .and_then(|c| map.color(&c)),
+                (_, _, Some(map)) => channel.and_then(|c| values.get(c)).map(|v| map.color(v)),
+                _ => color,
+            }
+        };
+        Self::of(sliced, &db.config.style, color_of)
+    }
+
+    /// Every rect of `sliced` as an outline, and every line, all stroked with `color`,
+    /// so that it can be laid over another db without hiding it.
+    pub fn outlined(sliced: &SlicedDb, color: Color) -> Self {
+        let mut res = Self::default();
+        let color = color32(color);
+        for r in sliced.rects.values() {
+            let width = r.style.width.unwrap_or(1.5);
+            res.outlines.push((r.geo, Pen::new(width, color, &r.style)));
+        }
+        for l in sliced.lines.values() {
+            let width = l.style.width.unwrap_or(1.0);
+            res.lines.push((l.geo, Pen::new(width, color, &l.style)));
+        }
+        let lines = sliced.lines.values().map(|l| l.geo.bbox());
+        res.bbox = Rect::bounding(sliced.bbox().into_iter().chain(lines));
+        res
+    }
+
+    /// The density map, `nx` × `ny` bins over the whole db, and the overlap regions of `sliced`,
+    /// to be drawn over its shapes.
+    pub fn overlays(
+        db: &Db,
+        sliced: &SlicedDb,
+        density: Option<(usize, usize)>,
+        overlaps: bool,
+    ) -> Self {
+        let mut res = Self::default();
+        if let (Some((nx, ny)), Some(die)) = (density, db.bbox()) {
+            let map = sliced.density(die, nx, ny);
+            let fallback = colorgrad::preset::viridis();
+            let grad = db.config.color.gradient().unwrap_or(&fallback);
+            let (nx, ny) = (map.nx, map.ny);
+            for (ix, iy) in (0..ny).flat_map(|iy| (0..nx).map(move |ix| (ix, iy))) {
+                let density = map.at(ix, iy);
+                if density > 0.0 {
+                    let c = Color::from(&grad.at(density.clamp(0.0, 1.0) as f32));
+                    let alpha = db.config.style.density_alpha;
+                    let c = Color32::from_rgba_unmultiplied(c.r, c.g, c.b, alpha);
+                    res.fills.push((map.bin(ix, iy), c));
+                }
+            }
+        }
+        if overlaps {
+            let regions = sliced.overlaps().pairs.into_iter().map(|o| o.region);
+            let color = color32(db.config.style.overlap_color);
+            res.fills.extend(regions.map(|r| (r, color)));
+        }
+        res
+    }
+
+    /// What changed from `before` to `after`: added shapes in green, deleted ones in red,
+    /// modified ones in orange, over the old geometry in a dashed outline, and the rest in light gray.
+    pub fn diff(before: &SlicedDb, after: &SlicedDb) -> Self {
+        const ADDED: Color32 = Color32::from_rgb(0x2c, 0xa0, 0x2c);
+        const DELETED: Color32 = Color32::from_rgb(0xd6, 0x27, 0x28);
+        const MODIFIED: Color32 = Color32::from_rgb(0xff, 0x7f, 0x0e);
+        let unchanged = Color32::LIGHT_GRAY.gamma_multiply(0.5);
+        let old = Pen {
+            stroke: Stroke::new(1.0, MODIFIED),
+            dash: Some(4.0),
+        };
+        let solid = |color| Pen {
+            stroke: Stroke::new(1.0, color),
+            dash: None,
+        };
+        let changes = before.changes(after);
+        let kinds: HashMap<_, _> = changes
+            .iter()
+            .map(|c| ((c.shape, &c.name), c.kind))
+            .collect();
+        let mut res = Self::default();
+        for (name, r) in after.rects.iter() {
+            match kinds.get(&("rect", name)) {
+                None => res.fills.push((r.geo, unchanged)),
+                Some(ChangeKind::Added) => res.fills.push((r.geo, ADDED)),
+                Some(_) => {
+                    let b = &before.rects[name];
+                    if b.geo != r.geo {
+                        res.outlines.push((b.geo, old));
+                    }
+                    res.fills.push((r.geo, MODIFIED));
+                }
+            }
+        }
+        for (name, l) in after.lines.iter() {
+            match kinds.get(&("line", name)) {
+                None => res.lines.push((l.geo, solid(unchanged))),
+                Some(ChangeKind::Added) => res.lines.push((l.geo, solid(ADDED))),
+                Some(_) => {
+                    let b = &before.lines[name];
+                    if b.geo != l.geo {
+                        res.lines.push((b.geo, old));
+                    }
+                    res.lines.push((l.geo, solid(MODIFIED)));
+                }
+            }
+        }
+        for change in changes.iter().filter(|c| c.kind == ChangeKind::Deleted) {
+            match change.shape {
+                "rect" => res.fills.push((before.rects[&change.name].geo, DELETED)),
+                _ => res
+                    .lines
+                    .push((before.lines[&change.name].geo, solid(DELETED))),
+            }
+        }
+        let bboxes = [before.bbox(), after.bbox()].into_iter().flatten();
+        let lines = before.lines.values().chain(after.lines.values());
+        res.bbox = Rect::bounding(bboxes.chain(lines.map(|l| l.geo.bbox())));
+        res
+    }
+
+    /// Paint the shapes in view, in screen space. Rects smaller than `LOD_SIZE` become dots,
+    /// and dots falling on the same pixel are drawn once.
+    pub fn paint(&self, transform: &PlotTransform, shapes: &mut Vec<Shape>) {
+        let bounds = transform.bounds();
+        let view = Rect::new(
+            Point::new(bounds.min()[0], bounds.min()[1]),
+            Point::new(bounds.max()[0], bounds.max()[1]),
+        );
+        let screen = |r: &Rect| {
+            transform.rect_from_values(
+                &PlotPoint::new(r.ll.x, r.ll.y),
+                &PlotPoint::new(r.ur.x, r.ur.y),
+            )
+        };
+        let mut mesh = Mesh::default();
+        let mut dashed = vec![];
+        let mut dots = HashSet::new();
+        let mut dot = |mesh: &mut Mesh, at: Pos2, color: Color32| {
+            let px = at.floor();
+            if dots.insert((px.x as i32, px.y as i32)) {
+                let rect = egui::Rect::from_min_size(px, egui::vec2(1.0, 1.0));
+                mesh.add_colored_rect(rect, color);
+            }
+        };
+        let tiny = |s: &egui::Rect| s.width() < LOD_SIZE && s.height() < LOD_SIZE;
+
+        for (rect, color) in self.fills.iter().filter(|(r, _)| r.intersects(&view)) {
+            let s = screen(rect);
+            if tiny(&s) {
+                dot(&mut mesh, s.center(), *color);
+            } else {
+                mesh.add_colored_rect(s, *color);
+            }
+        }
+        for (rect, pen) in self.outlines.iter().filter(|(r, _)| r.intersects(&view)) {
+            let s = screen(rect);
+            if tiny(&s) {
+                dot(&mut mesh, s.center(), pen.stroke.color);
+                continue;
+            }
+            let corners = [
+                s.left_bottom(),
+                s.right_bottom(),
+                s.right_top(),
+                s.left_top(),
+            ];
+            for i in 0..4 {
+                segment(
+                    &mut mesh,
+                    &mut dashed,
+                    corners[i],
+                    corners[(i + 1) % 4],
+                    pen,
+                );
+            }
+        }
+        for (line, pen) in self.lines.iter() {
+            if !line.bbox().intersects(&view) {
+                continue;
+            }
+            let a = transform.position_from_point(&PlotPoint::new(line.ll.x, line.ll.y));
+            let b = transform.position_from_point(&PlotPoint::new(line.ur.x, line.ur.y));
+            segment(&mut mesh, &mut dashed, a, b, pen);
+        }
+        shapes.push(Shape::mesh(mesh));
+        shapes.extend(dashed);
+    }
+}
+
+/// Add a stroke from `a` to `b` as a quad of `mesh`, or as a dashed line to `dashed`.
+fn segment(mesh: &mut Mesh, dashed: &mut Vec<Shape>, a: Pos2, b: Pos2, pen: &Pen) {
+    let Pen { stroke, dash } = *pen;
+    if stroke.width <= 0.0 {
+        return;
+    }
+    if let Some(dash) = dash {
+        dashed.extend(Shape::dashed_line(&[a, b], stroke, dash, dash));
+        return;
+    }
+    let d = b - a;
+    let n = if d.length() > 0.0 {
+        d.normalized().rot90() * stroke.width / 2.0
+    } else {
+        egui::vec2(0.0, stroke.width / 2.0)
+    };
+    let idx = mesh.vertices.len() as u32;
+    for p in [a + n, b + n, b - n, a - n] {
+        mesh.colored_vertex(p, stroke.color);
+    }
+    mesh.add_triangle(idx, idx + 1, idx + 2);
+    mesh.add_triangle(idx, idx + 2, idx + 3);
+}
+
+/// A `Batch` as an item of an `egui_plot::Plot`, drawn with the plot items around it.
+pub struct BatchItem(pub Arc<Batch>);
+
+impl PlotItem for BatchItem {
+    fn shapes(&self, _ui: &egui::Ui, transform: &PlotTransform, shapes: &mut Vec<Shape>) {
+        self.0.paint(transform, shapes);
+    }
+
+    fn initialize(&mut self, _x_range: RangeInclusive<f64>) {}
+
+    fn name(&self) -> &str {
+        ""
+    }
+
+    fn color(&self) -> Color32 {
+        DEFAULT_COLOR
+    }
+
+    fn highlight(&mut self) {}
+
+    fn highlighted(&self) -> bool {
+        false
+    }
+
+    fn geometry(&self) -> PlotGeometry<'_> {
+        PlotGeometry::None
+    }
+
+    fn bounds(&self) -> PlotBounds {
+        match self.0.bbox {
+            Some(r) => PlotBounds::from_min_max([r.ll.x, r.ll.y], [r.ur.x, r.ur.y]),
+            None => PlotBounds::NOTHING,
+        }
+    }
+
+    fn id(&self) -> Option<egui::Id> {
+        None
+    }
+}
+
+/// What a `Scene` is built for, it is rebuilt when any of these changes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SceneKey {
+    pub version: VersionId,
+    /// `Db::version`, which grows as commits come in.
+    pub head: VersionId,
+    pub overlaps: bool,
+    /// Number of density bins along x and y, if the density map is shown.
+    pub density: Option<(usize, usize)>,
+    pub displacement_from: Option<VersionId>,
+    /// Draw the shapes as outlines of this color, see `Batch::outlined`, instead of coloring them.
+    pub outline: Option<Color>,
+}
+
+/// Everything drawn for a version, built once and reused by every frame until its `key` changes.
+pub struct Scene {
+    pub key: SceneKey,
+    pub sliced: SlicedDb,
+    /// Every value of the gradient channel in this version, sorted.
+    pub values: Vec<f32>,
+    pub shapes: Arc<Batch>,
+    /// Density bins and overlap regions, drawn over the shapes.
+    pub overlays: Arc<Batch>,
+    /// Moves of the rects since `SceneKey::displacement_from`.
+    pub arrows: Vec<Vector>,
+}
+
+#[test]
+fn level_of_detail() {
+    use crate::{gui::public::Config, public::parse};
+
+    let mut script = String::from("COMMIT\nADDRECT big 0 0 100 100 #ff0000 ;\n");
+    for i in 0..10 {
+        script.push_str(&format!(
+            "ADDRECT t{} {} 0 {} 0.1 #0000ff ;\n",
+            i,
+            i as f64 * 0.01,
+            i as f64 * 0.01 + 0.01
+        ));
+    }
+    script.push_str("ADDRECT far 1000 1000 1001 1001 #00ff00 ;\n");
+    let db = parse(script, Config::default()).unwrap();
+    let batch = Batch::of(
+        &db.slice(VersionId(1)),
+        &LayerStyle::default(),
+        |_, _, c, _| c,
+    );
+    assert_eq!(batch.fills.len(), 12);
+    assert_eq!(
+        batch.bbox,
+        Some(Rect::new(Point::new(0.0, 0.0), Point::new(1001.0, 1001.0)))
+    );
+
+    // 100 units over 100 points: the tiny rects all fall on the same pixel, `far` is out of view.
+    let frame = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(100.0, 100.0));
+    let bounds = PlotBounds::from_min_max([0.0, 0.0], [100.0, 100.0]);
+    let transform = PlotTransform::new(frame, bounds, false, false);
+    let mut shapes = vec![];
+    batch.paint(&transform, &mut shapes);
+    let Shape::Mesh(mesh) = &shapes[0] else {
+        panic!("Fills are painted as a mesh.");
+    };
+    // One quad for `big`, one for the dot.
+    assert_eq!(mesh.vertices.len(), 8);
+}
+
+#[test]
+fn outlined() {
+    use crate::{gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 1 1 #ff0000 ;\nADDLINE L 0 2 2 2 width=3 ;\n".into(),
+        Config::default(),
+    )
+    .unwrap();
+    let blue = Color::rgb(0, 0, 255);
+    let batch = Batch::outlined(&db.slice(VersionId(1)), blue);
+    assert!(batch.fills.is_empty());
+    assert_eq!(batch.outlines.len(), 1);
+    assert_eq!(batch.outlines[0].1.stroke.color, color32(blue));
+    assert_eq!(batch.lines[0].1.stroke, Stroke::new(3.0, color32(blue)));
+}
+
+#[test]
+fn diff_batch() {
+    use crate::{gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 1 1 ;\nADDRECT B 2 0 3 1 ;\nADDRECT C 4 0 5 1 ;\nADDLINE L 0 2 2 2 ;\nCOMMIT\nUPDRECT A 0 0 2 2 ;\nDELRECT B ;\nADDLINE M 0 3 2 3 ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let batch = Batch::diff(&db.slice(VersionId(1)), &db.slice(VersionId(2)));
+    let fills: Vec<_> = batch.fills.iter().map(|(r, _)| r.ll.x).collect();
+    // `A` and `C` as they are now, then the deleted `B`.
+    assert_eq!(fills, [0.0, 4.0, 2.0]);
+    assert_ne!(batch.fills[0].1, batch.fills[1].1);
+    assert_ne!(batch.fills[0].1, batch.fills[2].1);
+    // The old geometry of `A`, dashed.
+    assert_eq!(batch.outlines.len(), 1);
+    assert!(batch.outlines[0].1.dash.is_some());
+    assert_eq!(batch.lines.len(), 2);
+}
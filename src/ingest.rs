@@ -0,0 +1,390 @@
+use std::{fmt, io, net::SocketAddr, path::PathBuf, str::FromStr, sync::Arc, time::Duration};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    net::TcpListener,
+    sync::{mpsc, watch},
+};
+
+use crate::{
+    db::version_controller::Commit,
+    parser::{error::ParseError, stream::StreamParser},
+};
+
+/// How often a followed file is checked for new bytes once its end is reached.
+pub const POLL: Duration = Duration::from_millis(100);
+
+/// Commits waiting for the GUI. When it falls behind, the readers stop reading until it catches up,
+/// and the writers on the other end of a pipe or socket block.
+pub const BACKLOG: usize = 1024;
+
+const CHUNK: usize = 64 * 1024;
+
+/// Where commits are read from, in the textual format of the scripts.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Source {
+    /// A script which keeps growing, read `tail -f` style.
+    File(PathBuf),
+    Stdin,
+    /// A Unix domain socket accepting any number of writers.
+    #[cfg(unix)]
+    Unix(PathBuf),
+    /// A TCP socket accepting any number of writers.
+    Tcp(SocketAddr),
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    /// `-` for stdin, `unix:PATH`, `tcp:ADDR` or a path to follow.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "-" {
+            return Ok(Source::Stdin);
+        }
+        #[cfg(unix)]
+        if let Some(path) = s.strip_prefix("unix:") {
+            return Ok(Source::Unix(path.into()));
+        }
+        if let Some(addr) = s.strip_prefix("tcp:") {
+            return addr
+                .parse()
+                .map(Source::Tcp)
+                .map_err(|e| format!("bad address `{}`: {}", addr, e));
+        }
+        Ok(Source::File(s.into()))
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Stdin => write!(f, "stdin"),
+            #[cfg(unix)]
+            Source::Unix(path) => write!(f, "unix {}", path.display()),
+            Source::Tcp(addr) => write!(f, "tcp {}", addr),
+        }
+    }
+}
+
+/// What the GUI shows of a source.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Status {
+    /// The source, with the port actually bound for TCP.
+    pub source: String,
+    /// Writers connected, or 1 while a file or stdin is read.
+    pub connections: usize,
+    /// Commits handed to the GUI so far.
+    pub received: usize,
+    /// The last failure, of the source or of a connection.
+    pub error: Option<String>,
+    /// The source has ended, and nothing more will come.
+    pub closed: bool,
+}
+
+/// The receiving end of a source, held by the GUI.
+pub struct Ingest {
+    pub commits: mpsc::Receiver<Commit>,
+    pub status: watch::Receiver<Status>,
+}
+
+/// Start reading `source` on the tokio runtime.
+pub fn start(source: Source) -> Ingest {
+    let (tx, commits) = mpsc::channel(BACKLOG);
+    let (status_tx, status) = watch::channel(Status::default());
+    tokio::spawn(serve(source, tx, status_tx));
+    Ingest { commits, status }
+}
+
+/// Send the commits of `source` on `tx` until the source ends or the receiver is dropped.
+pub async fn serve(
+    source: Source,
+    tx: mpsc::Sender<Commit>,
+    status: watch::Sender<Status>,
+) -> io::Result<()> {
+    status.send_modify(|s| s.source = source.to_string());
+    let sink = Sink {
+        tx,
+        status: Arc::new(status),
+    };
+    let res = match source {
+        Source::File(path) => {
+            let _reader = Connection::open(&sink.status);
+            follow(path, &sink).await
+        }
+        Source::Stdin => {
+            let _reader = Connection::open(&sink.status);
+            read(tokio::io::stdin(), &sink).await
+        }
+        #[cfg(unix)]
+        Source::Unix(path) => {
+            use std::os::unix::fs::FileTypeExt;
+
+            // A socket left behind by an earlier run.
+            if std::fs::metadata(&path).is_ok_and(|m| m.file_type().is_socket()) {
+                std::fs::remove_file(&path)?;
+            }
+            let listener = tokio::net::UnixListener::bind(&path)?;
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => sink.connect(stream),
+                        Err(e) => sink.refused(e).await,
+                    },
+                    _ = sink.tx.closed() => break Ok(()),
+                }
+            }
+        }
+        Source::Tcp(addr) => {
+            let listener = TcpListener::bind(addr).await?;
+            let bound = Source::Tcp(listener.local_addr()?).to_string();
+            sink.status.send_modify(|s| s.source = bound);
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => sink.connect(stream),
+                        Err(e) => sink.refused(e).await,
+                    },
+                    _ = sink.tx.closed() => break Ok(()),
+                }
+            }
+        }
+    };
+    // Writers already connected are still read, and leave `connections` as they end.
+    sink.status.send_modify(|s| {
+        s.closed = true;
+        if let Err(e) = &res {
+            s.error = Some(e.to_string());
+        }
+    });
+    res
+}
+
+/// Where the readers send their commits.
+#[derive(Clone)]
+struct Sink {
+    tx: mpsc::Sender<Commit>,
+    status: Arc<watch::Sender<Status>>,
+}
+
+impl Sink {
+    /// Send `commits`, waiting while the GUI is behind. Returns `false` once the GUI is gone.
+    async fn send(&self, commits: Vec<Commit>) -> bool {
+        for commit in commits {
+            if self.tx.send(commit).await.is_err() {
+                return false;
+            }
+            self.status.send_modify(|s| s.received += 1);
+        }
+        true
+    }
+
+    /// Read the commits of a new connection, on a task of its own.
+    fn connect(&self, stream: impl AsyncRead + Unpin + Send + 'static) {
+        let sink = self.clone();
+        let connection = Connection::open(&sink.status);
+        tokio::spawn(async move {
+            if let Err(e) = read(stream, &sink).await {
+                sink.status.send_modify(|s| s.error = Some(e.to_string()));
+            }
+            drop(connection);
+        });
+    }
+
+    /// Report a connection which could not be accepted, and wait a little before accepting
+    /// the next one, as the error tends to last, such as when running out of file descriptors.
+    async fn refused(&self, e: io::Error) {
+        self.status
+            .send_modify(|s| s.error = Some(format!("cannot accept a writer: {}", e)));
+        tokio::time::sleep(POLL).await;
+    }
+
+    /// Report text which is not a script. The commits around it still come.
+    fn malformed(&self, e: ParseError) {
+        self.status.send_modify(|s| s.error = Some(e.to_string()));
+    }
+}
+
+/// A writer counted in `Status::connections` for as long as it lives,
+/// even when the task reading it ends early or panics.
+struct Connection(Arc<watch::Sender<Status>>);
+
+impl Connection {
+    fn open(status: &Arc<watch::Sender<Status>>) -> Self {
+        status.send_modify(|s| s.connections += 1);
+        Self(status.clone())
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.send_modify(|s| s.connections -= 1);
+    }
+}
+
+/// Send the commits read from `reader` until it ends, the last one included.
+async fn read(mut reader: impl AsyncRead + Unpin, sink: &Sink) -> io::Result<()> {
+    let mut parser = StreamParser::new();
+    let mut buf = vec![0; CHUNK];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        let commits = if n == 0 {
+            parser.finish()
+        } else {
+            parser.feed(&buf[..n])
+        };
+        let commits = commits.unwrap_or_else(|e| {
+            sink.malformed(e);
+            vec![]
+        });
+        if !sink.send(commits).await || n == 0 {
+            return Ok(());
+        }
+    }
+}
+
+/// Read the script at `path` from the start, then keep reading what is appended to it, `tail -f` style.
+/// As the file never ends, its last commit waits for the writer to start another,
+/// or to end the file with a bare `COMMIT` line.
+async fn follow(path: PathBuf, sink: &Sink) -> io::Result<()> {
+    let mut file = tokio::fs::File::open(&path).await?;
+    let mut parser = StreamParser::new();
+    let mut buf = vec![0; CHUNK];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            if sink.tx.is_closed() {
+                return Ok(());
+            }
+            tokio::time::sleep(POLL).await;
+            continue;
+        }
+        let commits = parser.feed(&buf[..n]).unwrap_or_else(|e| {
+            sink.malformed(e);
+            vec![]
+        });
+        if !sink.send(commits).await {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use tokio::{io::AsyncWriteExt, sync::mpsc};
+
+    use super::*;
+
+    async fn next(rx: &mut mpsc::Receiver<Commit>) -> Commit {
+        tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn sources() {
+        assert_eq!("-".parse(), Ok(Source::Stdin));
+        assert_eq!(
+            "tcp:127.0.0.1:7878".parse(),
+            Ok(Source::Tcp(([127, 0, 0, 1], 7878).into()))
+        );
+        assert!("tcp:localhost".parse::<Source>().is_err());
+        assert_eq!("run.geo".parse(), Ok(Source::File("run.geo".into())));
+    }
+
+    #[tokio::test]
+    async fn follow_appends() {
+        let path = std::env::temp_dir().join(format!("geo_git_follow_{}.txt", std::process::id()));
+        let mut file = tokio::fs::File::create(&path).await.unwrap();
+        file.write_all(b"COMMIT\nADDRECT A 0 0 1 1 ;\n")
+            .await
+            .unwrap();
+        file.flush().await.unwrap();
+
+        let mut ingest = start(Source::File(path.clone()));
+        // The first commit is complete once the second one starts.
+        file.write_all(b"COMMIT \"grow\"\nUPDRECT A 0 0 2 2 ;\nCOMMIT\n")
+            .await
+            .unwrap();
+        file.flush().await.unwrap();
+        assert_eq!(next(&mut ingest.commits).await.rect_actions.len(), 1);
+        assert_eq!(
+            next(&mut ingest.commits).await.message.as_deref(),
+            Some("grow")
+        );
+        tokio::fs::remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn tcp_clients() {
+        let mut ingest = start(Source::Tcp(([127, 0, 0, 1], 0).into()));
+        let status = ingest
+            .status
+            .wait_for(|s| s.source.starts_with("tcp ") && !s.source.ends_with(":0"))
+            .await
+            .unwrap()
+            .clone();
+        let addr: SocketAddr = status.source.strip_prefix("tcp ").unwrap().parse().unwrap();
+
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(
+                b"COMMIT \"one\"\nADDRECT A 0 0 1 1 ;\nCOMMIT \"two\"\nADDRECT B 1 1 2 2 ;\n",
+            )
+            .await
+            .unwrap();
+        // The last commit of a connection comes once it closes.
+        assert_eq!(
+            next(&mut ingest.commits).await.message.as_deref(),
+            Some("one")
+        );
+        client.shutdown().await.unwrap();
+        drop(client);
+        assert_eq!(
+            next(&mut ingest.commits).await.message.as_deref(),
+            Some("two")
+        );
+        let status = ingest
+            .status
+            .wait_for(|s| s.connections == 0)
+            .await
+            .unwrap();
+        assert_eq!(status.received, 2);
+        assert_eq!(status.error, None);
+    }
+
+    #[tokio::test]
+    async fn malformed_clients() {
+        let mut ingest = start(Source::Tcp(([127, 0, 0, 1], 0).into()));
+        let status = ingest
+            .status
+            .wait_for(|s| s.source.starts_with("tcp ") && !s.source.ends_with(":0"))
+            .await
+            .unwrap()
+            .clone();
+        let addr: SocketAddr = status.source.strip_prefix("tcp ").unwrap().parse().unwrap();
+
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"COMMIT \"bad\"\nADDRECT A 0 0 1 width=x ;\nCOMMIT \"good\"\nADDRECT B 0 0 1 1 ;\n")
+            .await
+            .unwrap();
+        client.shutdown().await.unwrap();
+        drop(client);
+        // The bad commit is reported, the connection goes on with the next one.
+        assert_eq!(
+            next(&mut ingest.commits).await.message.as_deref(),
+            Some("good")
+        );
+        let status = ingest
+            .status
+            .wait_for(|s| s.connections == 0)
+            .await
+            .unwrap();
+        assert_eq!(status.received, 1);
+        assert!(status.error.as_deref().is_some_and(|e| e.contains("width")));
+    }
+}
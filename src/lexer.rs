@@ -18,7 +18,13 @@ pub struct Token {
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TokenKind {
     Str,
-    Literal { val: f64 },
+    Literal {
+        val: f64,
+    },
+    /// A `"`-quoted string, which may not span lines. `\"`, `\\` and `\n` are escapes.
+    Quoted {
+        terminated: bool,
+    },
     Semicolon,
     Eof,
 }
@@ -53,13 +59,30 @@ impl<'a> Cursor<'a> {
                 let val = self.number();
                 TokenKind::Literal { val }
             }
+            '-' | '+' if self.second().is_ascii_digit() => {
+                let negative = self.bump() == Some('-');
+                let val = self.number();
+                TokenKind::Literal {
+                    val: if negative { -val } else { val },
+                }
+            }
             EOF_CHAR => {
-                return Token::new(TokenKind::Eof, 0);
+                return Token {
+                    start,
+                    end: start,
+                    ..Token::new(TokenKind::Eof, 0)
+                };
             }
             ';' => {
                 self.bump();
                 TokenKind::Semicolon
             }
+            '"' => {
+                self.bump();
+                TokenKind::Quoted {
+                    terminated: self.eat_quoted(),
+                }
+            }
             _ => {
                 self.eat_identifier();
                 TokenKind::Str
@@ -91,6 +114,12 @@ impl<'a> Cursor<'a> {
         self.chars.clone().next().unwrap_or(EOF_CHAR)
     }
 
+    pub(crate) fn second(&self) -> char {
+        let mut iter = self.chars.clone();
+        iter.next();
+        iter.next().unwrap_or(EOF_CHAR)
+    }
+
     pub(crate) fn is_eof(&self) -> bool {
         self.chars.as_str().is_empty()
     }
@@ -106,24 +135,28 @@ impl<'a> Cursor<'a> {
         }
     }
 
-    pub(crate) fn eat_demical_digits(&mut self) -> Option<usize> {
-        let mut res = None;
+    /// Eats a quoted string up to its closing `"`, which is eaten too.
+    /// Returns `false` if the line or the text ends first.
+    fn eat_quoted(&mut self) -> bool {
         loop {
-            let x = self.first();
-            match x {
-                '0'..='9' => {
-                    let p = x as usize - '0' as usize;
-                    if res.is_none() {
-                        res = Some(p);
-                    } else {
-                        res = Some(res.unwrap() * 10 + p);
+            match self.first() {
+                _ if self.is_eof() => return false,
+                '\n' => return false,
+                '"' => {
+                    self.bump();
+                    return true;
+                }
+                '\\' => {
+                    self.bump();
+                    if self.first() != '\n' {
+                        self.bump();
                     }
+                }
+                _ => {
                     self.bump();
                 }
-                _ => break,
             }
         }
-        res
     }
 
     fn eat_identifier(&mut self) {
@@ -132,22 +165,15 @@ impl<'a> Cursor<'a> {
     }
 
     pub(crate) fn number(&mut self) -> f64 {
-        let mut res = self.eat_demical_digits().unwrap() as f64;
-        match self.first() {
-            '.' => {
-                self.bump();
-                let sub = self.eat_demical_digits().unwrap();
-                let mut real_sub = sub as f64;
-                while real_sub > 1.0 {
-                    real_sub /= 10.0;
-                }
-                if sub == 0 {
-                    res += real_sub;
-                }
-            }
-            _ => {}
-        };
-        res
+        let text = self.chars.as_str();
+        self.eat_while(|x| x.is_ascii_digit());
+        if self.first() == '.' {
+            self.bump();
+            self.eat_while(|x| x.is_ascii_digit());
+        }
+        let len = text.len() - self.chars.as_str().len();
+        // Digits with at most one `.`, which always make a number.
+        text[..len].parse().unwrap_or_default()
     }
 
     pub(crate) fn reset_pos_within_token(&mut self) {
@@ -1,6 +1,9 @@
+pub mod analysis;
 pub mod db;
 pub mod geo;
 pub mod gui;
+pub mod ingest;
 pub mod lexer;
 pub mod parser;
 pub mod public;
+pub mod settings;
@@ -1,139 +1,391 @@
 use crate::{
-    db::version_controller::Action,
-    geo::{color::Color, line::Line, point::Point, rect::Rect},
+    db::{
+        r#impl::DEFAULT_CHANNEL,
+        version_controller::{Action, ActionKind},
+    },
+    geo::{
+        color::Color,
+        line::Line,
+        point::Point,
+        rect::Rect,
+        shape::{Shape, ShapeKind},
+        style::{Fill, Style},
+    },
     lexer::TokenKind,
 };
 
 use super::{
+    error::ParseError,
     r#impl::StringParser,
-    tokens::{next_ident, next_literal, next_semicolon, peek_token},
+    tokens::{next_ident, next_literal, next_semicolon, peek_token, unexpected},
 };
 
-pub fn next_action(parser: &mut StringParser) -> Action {
-    let token = next_ident(parser).unwrap();
-    let res = match token.to_ascii_uppercase().as_slice() {
-        b"ADDRECT" => {
-            let name = next_ident(parser).unwrap();
-            let llx = next_literal(parser).unwrap();
-            let lly = next_literal(parser).unwrap();
-            let urx = next_literal(parser).unwrap();
-            let ury = next_literal(parser).unwrap();
-            let mut res = Action {
-                action: crate::db::version_controller::ActionKind::Add,
-                name: String::from_utf8(name.to_vec()).unwrap(),
-                geo: crate::geo::shape::Shape::Rect(Rect {
-                    ll: Point { x: llx, y: lly },
-                    ur: Point { x: urx, y: ury },
-                }),
-                desc: None,
-                color: None,
-                gradient: None,
-            };
+/// Whether the four numbers of a geometry lead the clauses of an action.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Geometry {
+    Required,
+    Optional,
+}
 
-            let mut numbers = [0.0, 0.0, 0.0];
-            let mut cnt = 0;
-            loop {
-                let next_token = peek_token(parser);
-                match next_token.kind {
-                    TokenKind::Str | TokenKind::Eof => unimplemented!(),
-                    TokenKind::Literal { val } => {
-                        numbers[cnt] = val;
-                        next_literal(parser);
-                        cnt += 1;
-                    }
-                    TokenKind::Semicolon => {
-                        break;
+/// A color component, which must be an integer in `0..=255`; `offset` is the one of its token.
+fn component(value: f64, offset: usize) -> Result<u8, ParseError> {
+    if value.fract() == 0.0 && (0.0..=255.0).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(ParseError::new(
+            offset,
+            format!(
+                "requires a color component in 0..=255, found `{}` instead",
+                value
+            ),
+        ))
+    }
+}
+
+fn color_of(parser: &mut StringParser, n: usize) -> Result<Color, ParseError> {
+    let mut c = [u8::MAX; 4];
+    for channel in c.iter_mut().take(n) {
+        let offset = peek_token(parser).start;
+        *channel = component(next_literal(parser)?, offset)?;
+    }
+    Ok(Color {
+        r: c[0],
+        g: c[1],
+        b: c[2],
+        a: c[3],
+    })
+}
+
+fn hex_of(token: &str, offset: usize) -> Result<Color, ParseError> {
+    Color::from_hex(token).ok_or_else(|| {
+        ParseError::new(
+            offset,
+            format!("requires a `#rrggbb[aa]`, found `{}` instead", token),
+        )
+    })
+}
+
+/// A `key=value` clause: a `stroke=<color>`, `width=w`, `dash=d` or `style=fill|outline` style,
+/// a `desc=<text>` description such as a cell master, or a value on a named channel.
+/// `offset` is the one of the value.
+fn key_value(res: &mut Action, key: &str, value: &str, offset: usize) -> Result<(), ParseError> {
+    let requires = |what: &str| {
+        ParseError::new(
+            offset,
+            format!("`{}=` requires {}, found `{}` instead", key, what, value),
+        )
+    };
+    match key {
+        "stroke" => {
+            res.style.stroke = Some(
+                Color::from_name(value)
+                    .or_else(|| Color::from_hex(value))
+                    .ok_or_else(|| requires("a color"))?,
+            )
+        }
+        "width" => res.style.width = Some(value.parse().map_err(|_| requires("a number"))?),
+        "dash" => res.style.dash = Some(value.parse().map_err(|_| requires("a number"))?),
+        "desc" => res.desc = Some(value.to_owned()),
+        "style" => {
+            res.style.fill = Some(match value {
+                "fill" => Fill::Solid,
+                "outline" => Fill::Outline,
+                _ => return Err(requires("`fill` or `outline`")),
+            })
+        }
+        _ => res.values.push((
+            key.to_owned(),
+            value.parse::<f32>().map_err(|_| requires("a number"))?,
+        )),
+    }
+    Ok(())
+}
+
+/// Parse everything after the name of an action up to the `;`, the same way for every action and shape:
+/// the geometry, then any of the clauses
+/// - `rgb r g b`, `rgba r g b a`, `#rrggbb[aa]` or a named color such as `red`,
+/// - `val x`, a value on the default channel,
+/// - `key=value`, see `key_value`,
+///
+/// or bare numbers: one value, three RGB or four RGBA numbers.
+/// Returns the geometry, if any.
+fn next_clauses(
+    parser: &mut StringParser,
+    res: &mut Action,
+    geometry: Geometry,
+) -> Result<Option<[f64; 4]>, ParseError> {
+    let mut numbers = vec![];
+    let end = loop {
+        let token = peek_token(parser);
+        match token.kind {
+            TokenKind::Literal { .. } => numbers.push((next_literal(parser)?, token.start)),
+            TokenKind::Semicolon | TokenKind::Eof => break token.start,
+            TokenKind::Quoted { .. } => return Err(unexpected(parser, &token, "a clause")),
+            TokenKind::Str => {
+                let text = String::from_utf8_lossy(&next_ident(parser)?).into_owned();
+                if text.starts_with('#') {
+                    res.color = Some(hex_of(&text, token.start)?);
+                } else if let Some((key, value)) = text.split_once('=') {
+                    key_value(res, key, value, token.start + key.len() + 1)?;
+                } else {
+                    match text.to_ascii_lowercase().as_str() {
+                        "rgb" => res.color = Some(color_of(parser, 3)?),
+                        "rgba" => res.color = Some(color_of(parser, 4)?),
+                        "val" => res
+                            .values
+                            .push((DEFAULT_CHANNEL.to_owned(), next_literal(parser)? as f32)),
+                        name => {
+                            res.color = Some(
+                                Color::from_name(name)
+                                    .ok_or_else(|| unexpected(parser, &token, "a clause"))?,
+                            )
+                        }
                     }
                 }
             }
-            match numbers.len() {
-                1 => {
-                    res.gradient = Some(numbers[0] as f32);
-                }
-                3 => {
-                    let r = numbers[0];
-                    let g = numbers[1];
-                    let b = numbers[2];
-                    res.color = Some(Color {
-                        r: r as u8,
-                        g: g as u8,
-                        b: b as u8,
-                        a: 0,
-                    });
-                }
-                _ => unreachable!(),
-            }
-            res
         }
+    };
 
-        b"UPDRECT" => {
-            let name = next_ident(parser).unwrap();
-            let mut res = Action {
-                action: crate::db::version_controller::ActionKind::Modify,
-                name: String::from_utf8(name.to_vec()).unwrap(),
-                geo: crate::geo::shape::Shape::None,
-                desc: None,
-                color: None,
-                gradient: None,
-            };
-
-            let mut literals = vec![];
-            while peek_token(parser).kind != TokenKind::Semicolon
-                && peek_token(parser).kind != TokenKind::Eof
-            {
-                literals.push(next_literal(parser).unwrap());
-            }
-            literals.reverse();
-
-            if literals.len() >= 4 {
-                let llx = literals.pop().unwrap();
-                let lly = literals.pop().unwrap();
-                let urx = literals.pop().unwrap();
-                let ury = literals.pop().unwrap();
-                res.geo = crate::geo::shape::Shape::Rect(Rect {
-                    ll: Point { x: llx, y: lly },
-                    ur: Point { x: urx, y: ury },
-                });
-            }
-            if literals.len() >= 3 {
-                let r = literals.pop().unwrap() as u8;
-                let g = literals.pop().unwrap() as u8;
-                let b = literals.pop().unwrap() as u8;
-                res.color = Some(Color { r, g, b, a: 0 });
-            }
-            res
+    let geo = match geometry {
+        Geometry::Required if numbers.len() < 4 => {
+            return Err(ParseError::new(
+                end,
+                format!("requires a geometry, found {} numbers", numbers.len()),
+            ))
         }
-        b"DELRECT" => {
-            let name = next_ident(parser).unwrap();
-            Action {
-                action: crate::db::version_controller::ActionKind::Delete,
-                name: String::from_utf8(name.to_vec()).unwrap(),
-                geo: crate::geo::shape::Shape::None,
-                desc: None,
-                color: None,
-                gradient: None,
-            }
+        Geometry::Optional if numbers.len() < 4 => None,
+        _ => {
+            let geo = [numbers[0].0, numbers[1].0, numbers[2].0, numbers[3].0];
+            numbers.drain(..4);
+            Some(geo)
         }
-        b"ADDLINE" => {
-            let name = next_ident(parser).unwrap();
-            let llx = next_literal(parser).unwrap();
-            let lly = next_literal(parser).unwrap();
-            let urx = next_literal(parser).unwrap();
-            let ury = next_literal(parser).unwrap();
-            Action {
-                action: crate::db::version_controller::ActionKind::Add,
-                name: String::from_utf8(name.to_vec()).unwrap(),
-                geo: crate::geo::shape::Shape::Line(Line {
-                    ll: Point { x: llx, y: lly },
-                    ur: Point { x: urx, y: ury },
-                }),
-                desc: None,
-                color: None,
-                gradient: None,
-            }
+    };
+    let component = |(value, offset): (f64, usize)| component(value, offset);
+    match numbers[..] {
+        [] => {}
+        [(value, _)] => res.values.push((DEFAULT_CHANNEL.to_owned(), value as f32)),
+        [r, g, b] => res.color = Some(Color::rgb(component(r)?, component(g)?, component(b)?)),
+        [r, g, b, a] => {
+            res.color = Some(Color {
+                r: component(r)?,
+                g: component(g)?,
+                b: component(b)?,
+                a: component(a)?,
+            })
+        }
+        _ => {
+            return Err(ParseError::new(
+                end,
+                format!(
+                    "requires a value, RGB or RGBA, found {} numbers",
+                    numbers.len()
+                ),
+            ))
         }
-        _ => unreachable!(),
+    }
+    Ok(geo)
+}
+
+fn rect_of([llx, lly, urx, ury]: [f64; 4]) -> Shape {
+    Shape::Rect(Rect {
+        ll: Point { x: llx, y: lly },
+        ur: Point { x: urx, y: ury },
+    })
+}
+
+fn line_of([llx, lly, urx, ury]: [f64; 4]) -> Shape {
+    Shape::Line(Line {
+        ll: Point { x: llx, y: lly },
+        ur: Point { x: urx, y: ury },
+    })
+}
+
+/// The action and the shape named by an action keyword such as `ADDRECT`, in any case.
+pub fn action_of(keyword: &[u8]) -> Option<(ActionKind, ShapeKind)> {
+    match keyword.to_ascii_uppercase().as_slice() {
+        b"ADDRECT" => Some((ActionKind::Add, ShapeKind::Rect)),
+        b"UPDRECT" => Some((ActionKind::Modify, ShapeKind::Rect)),
+        b"DELRECT" => Some((ActionKind::Delete, ShapeKind::Rect)),
+        b"ADDLINE" => Some((ActionKind::Add, ShapeKind::Line)),
+        b"UPDLINE" => Some((ActionKind::Modify, ShapeKind::Line)),
+        b"DELLINE" => Some((ActionKind::Delete, ShapeKind::Line)),
+        _ => None,
+    }
+}
+
+pub fn next_action(parser: &mut StringParser) -> Result<Action, ParseError> {
+    let keyword = peek_token(parser);
+    let (action, kind) = next_ident(parser)
+        .ok()
+        .and_then(|token| action_of(&token))
+        .ok_or_else(|| unexpected(parser, &keyword, "an action or `COMMIT`"))?;
+    let name = next_ident(parser)?;
+    let mut res = Action {
+        action,
+        kind,
+        name: String::from_utf8_lossy(&name).into_owned(),
+        geo: Shape::None,
+        desc: None,
+        color: None,
+        values: vec![],
+        style: Style::default(),
     };
-    next_semicolon(parser);
-    res
+    let geometry = match action {
+        ActionKind::Add => Some(Geometry::Required),
+        ActionKind::Modify => Some(Geometry::Optional),
+        ActionKind::Delete => None,
+    };
+    if let Some(geometry) = geometry {
+        if let Some(geo) = next_clauses(parser, &mut res, geometry)? {
+            res.geo = match kind {
+                ShapeKind::Rect => rect_of(geo),
+                ShapeKind::Line => line_of(geo),
+            };
+        }
+    }
+    next_semicolon(parser)?;
+    Ok(res)
+}
+
+#[test]
+fn styled_actions() {
+    use crate::{db::version_controller::VersionId, gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 1 1 #ff8800cc stroke=#000000 width=2 dash=4 style=outline ;\nADDRECT B 0 0 1 1 10 20 30 40 ;\nADDLINE L 0 0 1 1 #00ff00 ;\nCOMMIT\nUPDRECT A width=3 ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let v2 = db.slice(VersionId(2));
+    let a = &v2.rects[&bytes::Bytes::from("A")];
+    assert_eq!(
+        a.style,
+        Style {
+            stroke: Some(Color::rgb(0, 0, 0)),
+            width: Some(3.0),
+            dash: Some(4.0),
+            fill: Some(Fill::Outline),
+        }
+    );
+    assert_eq!(
+        v2.rects[&bytes::Bytes::from("B")].color,
+        Some(Color {
+            r: 10,
+            g: 20,
+            b: 30,
+            a: 40
+        })
+    );
+    assert_eq!(
+        v2.lines[&bytes::Bytes::from("L")].color,
+        Some(Color::rgb(0, 255, 0))
+    );
+}
+
+#[test]
+fn same_clause_same_color() {
+    use crate::{db::version_controller::VersionId, gui::public::Config, public::parse};
+
+    for clause in [
+        "rgb 255 140 0",
+        "255 140 0",
+        "#ff8c00",
+        "darkorange",
+        "val -3",
+        "-3",
+    ] {
+        let db = parse(
+            format!(
+                "COMMIT\nADDRECT A 0 0 1 1 {c} ;\nADDRECT B 0 0 1 1 ;\nADDLINE L 0 0 1 1 {c} ;\nADDLINE M 0 0 1 1 ;\nCOMMIT\nUPDRECT B {c} ;\nUPDLINE M {c} ;\nUPDRECT A 0 0 2 2 ;\n",
+                c = clause
+            ),
+            Config::default(),
+        )
+        .unwrap();
+        let v1 = db.slice(VersionId(1));
+        let v2 = db.slice(VersionId(2));
+        let a = &v1.rects[&bytes::Bytes::from("A")];
+        let b = &v2.rects[&bytes::Bytes::from("B")];
+        let l = &v1.lines[&bytes::Bytes::from("L")];
+        let m = &v2.lines[&bytes::Bytes::from("M")];
+        assert_eq!((a.color, &a.values), (b.color, &b.values), "{}", clause);
+        assert_eq!((l.color, &l.values), (m.color, &m.values), "{}", clause);
+        assert_eq!((a.color, &a.values), (l.color, &l.values), "{}", clause);
+        // An update without a color keeps the one already there.
+        assert_eq!(
+            v2.rects[&bytes::Bytes::from("A")].color,
+            a.color,
+            "{}",
+            clause
+        );
+    }
+    assert_eq!(
+        Color::from_name("darkorange"),
+        Some(Color::rgb(255, 140, 0))
+    );
+}
+
+#[test]
+fn descriptions() {
+    use crate::{db::version_controller::VersionId, gui::public::Config, public::parse};
+
+    let db = parse(
+        "COMMIT\nADDRECT A 0 0 1 1 desc=INVX1 ;\nADDLINE N 0 0 1 1 desc=clk ;\nCOMMIT\nUPDRECT A desc=NAND2 ;\n"
+            .into(),
+        Config::default(),
+    )
+    .unwrap();
+    let desc = |v, name: &str| {
+        let sliced = db.slice(VersionId(v));
+        let name = bytes::Bytes::copy_from_slice(name.as_bytes());
+        let rect = sliced.rects.get(&name).and_then(|r| r.desc.clone());
+        rect.or_else(|| sliced.lines.get(&name).and_then(|l| l.desc.clone()))
+    };
+    assert_eq!(desc(1, "A"), Some("INVX1".into()));
+    assert_eq!(desc(1, "N"), Some("clk".into()));
+    assert_eq!(desc(2, "A"), Some("NAND2".into()));
+    assert_eq!(
+        crate::geo::color::Attribute::Desc.of(b"A", desc(2, "A").as_deref(), None),
+        Some("NAND2".into())
+    );
+}
+
+#[test]
+fn parse_errors() {
+    use crate::{lexer::Cursor, parser::r#impl::StringParser};
+    use bytes::Bytes;
+
+    let parse = |script: &str| {
+        let bytes = Bytes::copy_from_slice(script.as_bytes());
+        StringParser::new(&bytes, Cursor::new(script)).parse()
+    };
+    for (script, offset) in [
+        ("COMMIT\nADDRECT A 0 0 1 1 width=abc ;\n", 31),
+        ("COMMIT\nADDRECT A 0 0 1 1 color=#zz ;\n", 31),
+        ("COMMIT\nADDRECT A 0 0 1 1 #zz ;\n", 25),
+        ("COMMIT\nADDRECT A 0 0 1 1 fill=foo ;\n", 30),
+        ("COMMIT\nADDRECT A 0 0 1 1 style=foo ;\n", 31),
+        ("COMMIT\nADDRECT A 0 0 1 ;\n", 23),
+        ("COMMIT\nADDRECT A 0 0 1 1 rgb 1 2 ;\n", 33),
+        ("COMMIT\nADDRECT A 0 0 1 1 1 2 ;\n", 29),
+        ("COMMIT\nADDRECT A 0 0 1 1 foo ;\n", 25),
+        ("COMMIT\nDELRECT A 3 ;\n", 17),
+        ("COMMIT\nMOVRECT A ;\n", 7),
+        ("COMMIT\nADDRECT ;\n", 15),
+        ("COMMIT\nADDRECT A 0 0 1", 22),
+        ("COMMIT\nADDRECT A 0 0 1 1 rgb 300 0 0 ;\n", 29),
+        ("COMMIT\nADDRECT A 0 0 1 1 rgba 1 2 3 1.7 ;\n", 36),
+        ("COMMIT\nADDRECT A 0 0 1 1 1 -5 3 ;\n", 27),
+        ("COMMIT\nADDRECT A 0 0 1 1 1 2 3 256 ;\n", 31),
+    ] {
+        let err = parse(script).err();
+        assert_eq!(err.map(|e| e.offset), Some(offset), "{}", script);
+    }
+
+    assert!(parse("COMMIT\nADDRECT A 5. 0 1 99999999999999999999999 ;\n").is_ok());
+
+    let commits = parse("COMMIT\nDELLINE A ;\nDELRECT B\n").unwrap();
+    let kinds: Vec<_> = commits[0].rect_actions.iter().map(|a| a.kind).collect();
+    assert_eq!(kinds, vec![ShapeKind::Line, ShapeKind::Rect]);
 }
@@ -1,9 +1,14 @@
-use super::{r#impl::StringParser, tokens::next_ident};
+use super::{
+    error::ParseError,
+    r#impl::StringParser,
+    tokens::{next_ident, peek_token, unexpected},
+};
 
-pub fn next_commit(parser: &mut StringParser) {
-    let res = next_ident(parser).unwrap();
+pub fn next_commit(parser: &mut StringParser) -> Result<(), ParseError> {
+    let token = peek_token(parser);
+    let res = next_ident(parser)?;
     match res.to_ascii_uppercase().as_slice() {
-        b"" => (),
-        _ => unreachable!("What the hell have I just read, spit it out, sir."),
+        b"COMMIT" => Ok(()),
+        _ => Err(unexpected(parser, &token, "`COMMIT`")),
     }
 }
@@ -0,0 +1,26 @@
+use std::fmt::Display;
+
+/// Text which does not follow the script grammar.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseError {
+    /// Byte offset of the offending token in the script.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(offset: usize, message: impl Into<String>) -> Self {
+        Self {
+            offset,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "byte {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
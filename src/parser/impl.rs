@@ -4,12 +4,13 @@ use bytes::Bytes;
 
 use crate::{
     db::version_controller::Commit,
-    lexer::{Cursor, TokenKind},
+    lexer::{Cursor, Token, TokenKind},
 };
 
 use super::{
-    action::next_action,
-    tokens::{next_token, peek_token},
+    action::{action_of, next_action},
+    error::ParseError,
+    tokens::{next_token, peek_token, unexpected},
 };
 
 pub struct StringParser<'src> {
@@ -34,7 +35,39 @@ impl<'src> StringParser<'src> {
         self.src.slice(rng)
     }
 
-    pub fn parse(mut self) -> Vec<Commit> {
+    fn is_commit(&self, token: &Token) -> bool {
+        token.kind == TokenKind::Str
+            && self
+                .read_text(token.start..token.end)
+                .eq_ignore_ascii_case(b"COMMIT")
+    }
+
+    /// The message of a commit: the quoted string following `COMMIT`, if any.
+    fn message(&mut self) -> Result<Option<String>, ParseError> {
+        let token = peek_token(self);
+        let text = self.read_text(token.start..token.end);
+        match token.kind {
+            TokenKind::Quoted { terminated: true } => {
+                next_token(self);
+                let message = unquote(&String::from_utf8_lossy(&text[1..text.len() - 1]));
+                Ok((!message.is_empty()).then_some(message))
+            }
+            TokenKind::Quoted { terminated: false } => Err(ParseError::new(
+                token.start,
+                "the message is not closed by a `\"` on its line",
+            )),
+            TokenKind::Str if !self.is_commit(&token) && action_of(&text).is_none() => {
+                Err(unexpected(
+                    self,
+                    &token,
+                    "an action or a quoted message such as `COMMIT \"place\"`",
+                ))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub fn parse(mut self) -> Result<Vec<Commit>, ParseError> {
         let mut res = vec![];
         loop {
             let c = next_token(&mut self);
@@ -42,26 +75,84 @@ impl<'src> StringParser<'src> {
             if c.kind == TokenKind::Eof {
                 break;
             }
+            if !self.is_commit(&c) {
+                return Err(unexpected(&self, &c, "`COMMIT`"));
+            }
 
             let mut commit = Commit::new();
+            commit.message = self.message()?;
             'in_commit: loop {
-                let c = peek_token(&mut self);
-
-                if c.kind == TokenKind::Str {
-                    let txt = self.read_text(c.start..c.end);
-                    match txt.to_ascii_uppercase().as_slice() {
-                        b"COMMIT" => break 'in_commit,
-                        _ => {}
-                    }
+                let c = peek_token(&self);
+
+                if self.is_commit(&c) {
+                    break 'in_commit;
                 } else if c.kind == TokenKind::Eof {
                     break;
                 }
 
-                let action = next_action(&mut self);
+                let action = next_action(&mut self)?;
                 commit.add_action(action);
             }
             res.push(commit);
         }
-        res
+        Ok(res)
+    }
+}
+
+/// The text of a quoted string, without its escapes.
+fn unquote(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => res.push('\n'),
+                Some(c) => res.push(c),
+                None => {}
+            },
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// `text` as a quoted string, see `unquote`.
+pub fn quote(text: &str) -> String {
+    let mut res = String::with_capacity(text.len() + 2);
+    res.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            c => res.push(c),
+        }
     }
+    res.push('"');
+    res
+}
+
+#[test]
+fn messages() {
+    let parse = |script: &str| {
+        let bytes = Bytes::copy_from_slice(script.as_bytes());
+        StringParser::new(&bytes, Cursor::new(script)).parse()
+    };
+    let commits = parse(
+        "COMMIT \"place \\\"A\\\"\" ADDRECT A 0 0 1 1 ;\nCOMMIT ADDRECT B 0 0 1 1 ;\nCOMMIT \"two\\nlines\"\n",
+    )
+    .unwrap();
+    assert_eq!(commits[0].message.as_deref(), Some("place \"A\""));
+    assert_eq!(commits[0].rect_actions.len(), 1);
+    // An action on the line of `COMMIT` is not taken for a message.
+    assert_eq!(commits[1].message, None);
+    assert_eq!(commits[1].rect_actions.len(), 1);
+    assert_eq!(commits[2].message.as_deref(), Some("two\nlines"));
+    let quoted = quote("a \"b\" \\ c\nd");
+    assert_eq!(unquote(&quoted[1..quoted.len() - 1]), "a \"b\" \\ c\nd");
+
+    let err = parse("COMMIT place\nADDRECT A 0 0 1 1 ;\n").err();
+    assert_eq!(err.map(|e| e.offset), Some(7));
+    let err = parse("COMMIT \"place\nADDRECT A 0 0 1 1 ;\n").err();
+    assert_eq!(err.map(|e| e.offset), Some(7));
 }
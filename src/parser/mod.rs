@@ -1,4 +1,6 @@
 pub mod action;
 pub mod commit;
+pub mod error;
 pub mod r#impl;
+pub mod stream;
 pub mod tokens;
@@ -0,0 +1,108 @@
+use bytes::Bytes;
+
+use super::{error::ParseError, r#impl::StringParser};
+use crate::{db::version_controller::Commit, lexer::Cursor};
+
+/// Parses a script as it grows, a chunk of bytes at a time.
+/// A commit is handed out once the line starting the next one is complete, so that only the new
+/// commits are lexed and parsed, never the text before them.
+/// A line starts a commit when its first token is `COMMIT`; the quoted message and the actions
+/// which may follow on that line belong to the new commit.
+#[derive(Default)]
+pub struct StreamParser {
+    /// Text of the commits not handed out yet.
+    pending: Vec<u8>,
+    /// Length of the complete lines of `pending` already looked at.
+    scanned: usize,
+    /// Bytes of the script before `pending`, so that errors point into the whole script.
+    consumed: usize,
+}
+
+impl StreamParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `bytes` to the script, and return the commits they complete.
+    /// On an error, the text of the completed commits is dropped, and parsing goes on after it.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Vec<Commit>, ParseError> {
+        self.pending.extend_from_slice(bytes);
+        let mut ready = 0;
+        while let Some(len) = self.pending[self.scanned..]
+            .iter()
+            .position(|b| *b == b'\n')
+        {
+            let line = &self.pending[self.scanned..self.scanned + len];
+            let starts_commit = line
+                .split(|b| b.is_ascii_whitespace())
+                .find(|token| !token.is_empty())
+                .is_some_and(|token| token.eq_ignore_ascii_case(b"COMMIT"));
+            let first = self.pending[..self.scanned]
+                .iter()
+                .all(|b| b.is_ascii_whitespace());
+            if starts_commit && !first {
+                ready = self.scanned;
+            }
+            self.scanned += len + 1;
+        }
+        if ready == 0 {
+            return Ok(vec![]);
+        }
+        let rest = self.pending.split_off(ready);
+        self.scanned -= ready;
+        let text = std::mem::replace(&mut self.pending, rest);
+        self.parse_commits(&text)
+    }
+
+    /// The commits left when the script ends, the last one included.
+    pub fn finish(&mut self) -> Result<Vec<Commit>, ParseError> {
+        self.scanned = 0;
+        let text = std::mem::take(&mut self.pending);
+        self.parse_commits(&text)
+    }
+
+    fn parse_commits(&mut self, text: &[u8]) -> Result<Vec<Commit>, ParseError> {
+        let offset = self.consumed;
+        self.consumed += text.len();
+        let text = String::from_utf8_lossy(text);
+        if text.trim().is_empty() {
+            return Ok(vec![]);
+        }
+        let bytes = Bytes::copy_from_slice(text.as_bytes());
+        StringParser::new(&bytes, Cursor::new(&text))
+            .parse()
+            .map_err(|e| ParseError::new(offset + e.offset, e.message))
+    }
+}
+
+#[test]
+fn stream() {
+    let script = "COMMIT \"first\"\nADDRECT A 0 0 1 1 ;\nCOMMIT ADDRECT B 1 1 2 2 ;\nUPDRECT A 0 0 2 2 ;\nCOMMIT \"third\" DELRECT B ;\n";
+    let mut parser = StreamParser::new();
+    let mut commits = vec![];
+    // Chunks cut through lines and tokens.
+    for chunk in script.as_bytes().chunks(5) {
+        commits.extend(parser.feed(chunk).unwrap());
+    }
+    assert_eq!(commits.len(), 2);
+    assert_eq!(commits[0].message.as_deref(), Some("first"));
+    assert_eq!(commits[0].rect_actions.len(), 1);
+    assert_eq!(commits[1].rect_actions.len(), 2);
+
+    let last = parser.finish().unwrap();
+    assert_eq!(last.len(), 1);
+    assert_eq!(last[0].message.as_deref(), Some("third"));
+    assert!(parser.finish().unwrap().is_empty());
+
+    let mut parser = StreamParser::new();
+    let ok = parser
+        .feed(b"COMMIT\nADDRECT A 0 0 1 1 ;\nCOMMIT\n")
+        .unwrap();
+    assert_eq!(ok.len(), 1);
+    let err = parser
+        .feed(b"ADDRECT B 0 0 1 width=x ;\nCOMMIT\nADDRECT C 0 0 1 1 ;\n")
+        .err();
+    assert_eq!(err.map(|e| e.offset), Some(56));
+    // The bad commit is dropped, the next ones still come.
+    assert_eq!(parser.finish().unwrap().len(), 1);
+}
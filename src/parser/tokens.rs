@@ -1,60 +1,57 @@
 use bytes::Bytes;
 
-use crate::lexer::Token;
+use crate::lexer::{Token, TokenKind};
 
-use super::r#impl::StringParser;
+use super::{error::ParseError, r#impl::StringParser};
 
 pub fn next_token(parser: &mut StringParser) -> Token {
     parser.cursor.advance_token()
 }
-pub fn next_ident(parser: &mut StringParser) -> Option<Bytes> {
+
+/// An error at `token`, which is not the `expected` one.
+pub fn unexpected(parser: &StringParser, token: &Token, expected: &str) -> ParseError {
+    let found = match token.kind {
+        TokenKind::Eof => "the end of the script".to_owned(),
+        TokenKind::Semicolon => "`;`".to_owned(),
+        TokenKind::Str | TokenKind::Literal { .. } | TokenKind::Quoted { .. } => format!(
+            "`{}`",
+            String::from_utf8_lossy(&parser.read_text(token.start..token.end))
+        ),
+    };
+    ParseError::new(
+        token.start,
+        format!("requires {}, found {} instead", expected, found),
+    )
+}
+
+pub fn next_ident(parser: &mut StringParser) -> Result<Bytes, ParseError> {
     let token = parser.cursor.advance_token();
     match token.kind {
-        crate::lexer::TokenKind::Eof => {
-            return None;
-        }
-        crate::lexer::TokenKind::Str => {}
-        crate::lexer::TokenKind::Literal { val: _ } => {
-            unreachable!("Requires a ident. Found a literal instead.")
-        }
-        crate::lexer::TokenKind::Semicolon => {
-            unreachable!("Requires a ident. Found a `;` instead.")
-        }
-    };
-    Some(parser.read_text(token.start..token.end))
+        TokenKind::Str => Ok(parser.read_text(token.start..token.end)),
+        _ => Err(unexpected(parser, &token, "a name")),
+    }
 }
 
-pub fn next_literal(parser: &mut StringParser) -> Option<f64> {
+pub fn next_literal(parser: &mut StringParser) -> Result<f64, ParseError> {
     let token = parser.cursor.advance_token();
     match token.kind {
-        crate::lexer::TokenKind::Eof => {
-            return None;
-        }
-        crate::lexer::TokenKind::Str => unreachable!("Requires a literal. Found a string instead."),
-        crate::lexer::TokenKind::Literal { val: _ } => {}
-        crate::lexer::TokenKind::Semicolon => {
-            unreachable!("Requires a literal. Found a `;` instead.")
-        }
+        TokenKind::Literal { .. } => {}
+        _ => return Err(unexpected(parser, &token, "a number")),
     };
     let texted = parser.read_text(token.start..token.end);
-    let f64 = String::from_utf8(texted.to_vec())
-        .unwrap()
+    String::from_utf8_lossy(&texted)
         .parse::<f64>()
-        .unwrap();
-    // FIXME:
-    // `bytes` to usize
-    Some(f64)
+        .map_err(|_| unexpected(parser, &token, "a number"))
 }
 
-pub fn next_semicolon(parser: &mut StringParser) {
+/// The `;` ending an action, which may be left out at the end of the script.
+pub fn next_semicolon(parser: &mut StringParser) -> Result<(), ParseError> {
     let token = parser.cursor.advance_token();
     match token.kind {
-        crate::lexer::TokenKind::Eof => (),
-        crate::lexer::TokenKind::Str => unreachable!("Requires a `;`. Found a string instead."),
-        crate::lexer::TokenKind::Literal { val: _ } => {
-            unreachable!("Requires a `;`. Found a literal instead.")
+        TokenKind::Eof | TokenKind::Semicolon => Ok(()),
+        TokenKind::Str | TokenKind::Literal { .. } | TokenKind::Quoted { .. } => {
+            Err(unexpected(parser, &token, "a `;`"))
         }
-        crate::lexer::TokenKind::Semicolon => (),
     }
 }
 
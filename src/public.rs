@@ -1,17 +1,61 @@
 use bytes::Bytes;
 
-use crate::{db::r#impl::Db, gui::public::Config, lexer::Cursor, parser::r#impl::StringParser};
+use crate::{
+    db::{error::DbError, r#impl::Db},
+    gui::public::Config,
+    lexer::Cursor,
+    parser::{error::ParseError, r#impl::StringParser},
+};
 
-pub fn parse(x: String, cfg: Config) -> Db {
+/// Why a script cannot be turned into a `Db`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ScriptError {
+    /// The text does not follow the grammar.
+    Parse(ParseError),
+    /// A commit is rejected by the db.
+    Db(DbError),
+}
+
+impl From<ParseError> for ScriptError {
+    fn from(e: ParseError) -> Self {
+        ScriptError::Parse(e)
+    }
+}
+
+impl From<DbError> for ScriptError {
+    fn from(e: DbError) -> Self {
+        ScriptError::Db(e)
+    }
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScriptError::Parse(e) => write!(f, "{}", e),
+            ScriptError::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Parse a script into a `Db`, together with the actions skipped by `Config::policy`.
+pub fn parse_with_warnings(x: String, cfg: Config) -> Result<(Db, Vec<DbError>), ScriptError> {
     let mut db = Db::new(cfg);
     let cursor = Cursor::new(&x);
     let bytes = &Bytes::copy_from_slice(x.as_bytes());
     let parser = StringParser::new(bytes, cursor);
-    let commits = parser.parse();
+    let commits = parser.parse()?;
+    let mut warnings = vec![];
     for c in commits {
-        db.create_version(c);
+        warnings.extend(db.create_version(c)?);
     }
-    db
+    Ok((db, warnings))
+}
+
+/// Parse a script into a `Db`, dropping the warnings; see `parse_with_warnings` to get them.
+pub fn parse(x: String, cfg: Config) -> Result<Db, ScriptError> {
+    parse_with_warnings(x, cfg).map(|(db, _)| db)
 }
 
 #[test]
@@ -25,7 +69,7 @@ fn pp() {
     let mut raw_text = String::with_capacity(256);
     file.read_to_string(&mut raw_text).unwrap();
 
-    let db = parse(raw_text, Config::default());
+    let db = parse(raw_text, Config::default()).unwrap();
     let v0 = db.slice(VersionId(0));
     let v1 = db.slice(VersionId(1));
     let v2 = db.slice(VersionId(2));
@@ -62,7 +106,7 @@ fn real_test() {
     let mut raw_text = String::with_capacity(256);
     file.read_to_string(&mut raw_text).unwrap();
 
-    let db = parse(raw_text, Config::default());
+    let db = parse(raw_text, Config::default()).unwrap();
     let v0 = db.slice(VersionId(0));
     let v1 = db.slice(VersionId(1));
     let v2 = db.slice(VersionId(2));
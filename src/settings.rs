@@ -0,0 +1,497 @@
+use std::path::{Path, PathBuf};
+
+use eframe::egui::Key;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    geo::{
+        color::{self, Attribute, ColorType, Scale, ValueRange},
+        point::Point,
+        rect::Rect,
+    },
+    gui::{public::Config, scene::LayerStyle},
+};
+
+/// Names of a project-local config file, looked for in the working directory and its ancestors.
+pub const PROJECT_FILES: [&str; 2] = ["geo_git.toml", "geo_git.json"];
+
+/// How shapes are colored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorMode {
+    /// Map a value channel on a gradient.
+    #[default]
+    Gradient,
+    /// One palette color per category of shapes.
+    Categorical,
+    /// The colors written in the script.
+    Rgb,
+}
+
+/// How the range of gradient values is found, see `ValueRange`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Range {
+    Fixed,
+    #[default]
+    Global,
+    PerVersion,
+    Percentile,
+}
+
+/// What shapes are grouped by in categorical coloring, see `Attribute`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum By {
+    #[default]
+    Name,
+    /// The first `depth` components of the hierarchical name.
+    Prefix,
+    Desc,
+    /// The value on `channel`.
+    Channel,
+}
+
+/// The coloring of the dbs, in the terms of the command line.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorSettings {
+    pub mode: ColorMode,
+    /// Gradient preset, `magma` if `None`, or `rd_bu` when `diverging`.
+    pub preset: Option<String>,
+    pub range: Range,
+    /// Bounds of a fixed range.
+    pub min: f32,
+    pub max: f32,
+    /// Lower and upper quantile of a percentile range.
+    pub percentile: [f32; 2],
+    /// Spread the values over the gradient on a log scale.
+    pub log: bool,
+    /// Center the gradient on zero, with a diverging color scheme.
+    pub diverging: bool,
+    /// Value channel colored by the gradient, or grouped by `By::Channel`.
+    pub channel: String,
+    pub by: By,
+    /// Number of name components of `By::Prefix`.
+    pub depth: usize,
+    /// Palette of categorical coloring.
+    pub palette: String,
+}
+
+impl Default for ColorSettings {
+    fn default() -> Self {
+        Self {
+            mode: ColorMode::default(),
+            preset: None,
+            range: Range::default(),
+            min: 0.0,
+            max: 3000.0,
+            percentile: [0.02, 0.98],
+            log: false,
+            diverging: false,
+            channel: crate::db::r#impl::DEFAULT_CHANNEL.to_owned(),
+            by: By::default(),
+            depth: 1,
+            palette: color::PALETTES[0].name.to_owned(),
+        }
+    }
+}
+
+impl ColorSettings {
+    pub fn color_type(&self) -> Result<ColorType, String> {
+        Ok(match self.mode {
+            ColorMode::Gradient => {
+                let preset = match &self.preset {
+                    Some(name) => name,
+                    None if self.diverging => "rd_bu",
+                    None => "magma",
+                };
+                ColorType::Gradient {
+                    generator: color::preset(preset)
+                        .ok_or_else(|| format!("unknown gradient preset `{}`", preset))?,
+                    min: self.min,
+                    max: self.max,
+                    range: match self.range {
+                        Range::Fixed => ValueRange::Fixed,
+                        Range::Global => ValueRange::Global,
+                        Range::PerVersion => ValueRange::PerVersion,
+                        Range::Percentile => ValueRange::Percentile {
+                            low: self.percentile[0],
+                            high: self.percentile[1],
+                        },
+                    },
+                    scale: if self.log { Scale::Log } else { Scale::Linear },
+                    diverging: self.diverging,
+                    channel: self.channel.clone(),
+                }
+            }
+            ColorMode::Categorical => ColorType::Categorical {
+                palette: color::palette(&self.palette)
+                    .ok_or_else(|| format!("unknown palette `{}`", self.palette))?,
+                attribute: match self.by {
+                    By::Name => Attribute::Name,
+                    By::Prefix => Attribute::Prefix { depth: self.depth },
+                    By::Desc => Attribute::Desc,
+                    By::Channel => Attribute::Channel(self.channel.clone()),
+                },
+            },
+            ColorMode::Rgb => ColorType::RGB,
+        })
+    }
+
+    /// Take on `color`, as changed in the GUI. A gradient does not know its preset,
+    /// which is kept as it is.
+    pub fn update(&mut self, color: &ColorType) {
+        match color {
+            ColorType::RGB => self.mode = ColorMode::Rgb,
+            ColorType::Gradient {
+                min,
+                max,
+                range,
+                scale,
+                diverging,
+                channel,
+                ..
+            } => {
+                self.mode = ColorMode::Gradient;
+                (self.min, self.max) = (*min, *max);
+                self.range = match range {
+                    ValueRange::Fixed => Range::Fixed,
+                    ValueRange::Global => Range::Global,
+                    ValueRange::PerVersion => Range::PerVersion,
+                    ValueRange::Percentile { low, high } => {
+                        self.percentile = [*low, *high];
+                        Range::Percentile
+                    }
+                };
+                self.log = *scale == Scale::Log;
+                self.diverging = *diverging;
+                self.channel.clone_from(channel);
+            }
+            ColorType::Categorical { palette, attribute } => {
+                self.mode = ColorMode::Categorical;
+                self.palette = palette.name.to_owned();
+                self.by = match attribute {
+                    Attribute::Name => By::Name,
+                    Attribute::Prefix { depth } => {
+                        self.depth = *depth;
+                        By::Prefix
+                    }
+                    Attribute::Desc => By::Desc,
+                    Attribute::Channel(channel) => {
+                        self.channel.clone_from(channel);
+                        By::Channel
+                    }
+                };
+            }
+        }
+    }
+}
+
+/// Overlays shown when the GUI opens, and by `render`.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layers {
+    pub overlaps: bool,
+    pub density: bool,
+    /// Number of density bins along x and y.
+    pub density_bins: [usize; 2],
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Self {
+            overlaps: false,
+            density: false,
+            density_bins: [32, 32],
+        }
+    }
+}
+
+/// Keys of the GUI, by their `egui` names, such as `Left`, `Space` or `N`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeyBindings {
+    pub previous: String,
+    pub next: String,
+    pub first: String,
+    pub last: String,
+    pub play: String,
+    pub clear_selection: String,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            previous: Key::ArrowLeft.name().to_owned(),
+            next: Key::ArrowRight.name().to_owned(),
+            first: Key::Home.name().to_owned(),
+            last: Key::End.name().to_owned(),
+            play: Key::Space.name().to_owned(),
+            clear_selection: Key::Escape.name().to_owned(),
+        }
+    }
+}
+
+impl KeyBindings {
+    fn all(&self) -> [&str; 6] {
+        [
+            &self.previous,
+            &self.next,
+            &self.first,
+            &self.last,
+            &self.play,
+            &self.clear_selection,
+        ]
+    }
+}
+
+/// Bounds keeping the GUI responsive on large dbs and fast streams.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Streamed commits applied per frame, so that a burst does not freeze the GUI.
+    pub commits_per_frame: usize,
+    /// Largest number of density bins along x or y.
+    pub max_density_bins: usize,
+    /// Fastest playback, in commits per second.
+    pub max_rate: f32,
+    /// Categories shown in the legend of categorical coloring.
+    pub legend_entries: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            commits_per_frame: 256,
+            max_density_bins: 512,
+            max_rate: 1000.0,
+            legend_entries: 16,
+        }
+    }
+}
+
+/// Defaults of the command line and the GUI, read from config files, TOML or JSON by their extension.
+/// A file only needs the fields it changes.
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub color: ColorSettings,
+    pub layers: Layers,
+    pub style: LayerStyle,
+    /// Area shown when the GUI opens, and by `render` and `animate`: `[llx, lly, urx, ury]`.
+    /// The bounding box of the shapes if `None`.
+    pub viewport: Option<[f64; 4]>,
+    pub keys: KeyBindings,
+    pub limits: Limits,
+}
+
+impl Settings {
+    /// The user-level settings, overridden by the project-local file nearest to `dir`, then by `extra`.
+    pub fn load(dir: &Path, extra: Option<&Path>) -> Result<Self, String> {
+        Self::load_from(user_dir().as_deref(), dir, extra)
+    }
+
+    /// Like `Settings::load`, with the user-level file looked up in `user_dir` rather than the user's config directory.
+    pub fn load_from(
+        user_dir: Option<&Path>,
+        dir: &Path,
+        extra: Option<&Path>,
+    ) -> Result<Self, String> {
+        let files: Vec<_> = user_dir
+            .and_then(user_file)
+            .into_iter()
+            .chain(project_file(dir))
+            .chain(extra.map(Path::to_owned))
+            .collect();
+        let mut merged = Value::Object(Default::default());
+        for path in files.iter() {
+            merge(&mut merged, read(path)?);
+        }
+        Self::from_value(merged).map_err(|e| {
+            let files: Vec<_> = files.iter().map(|f| f.display().to_string()).collect();
+            format!("{}: {}", files.join(", "), e)
+        })
+    }
+
+    /// Settings from the merged content of config files. The names of the gradient preset and the palette
+    /// are only checked by `Settings::config`, once the command line had a chance to override them.
+    pub fn from_value(value: Value) -> Result<Self, String> {
+        let res: Self = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        if let Some(key) = res
+            .keys
+            .all()
+            .into_iter()
+            .find(|k| Key::from_name(k).is_none())
+        {
+            return Err(format!("unknown key `{}`", key));
+        }
+        Ok(res)
+    }
+
+    /// Write the settings to `path`, as JSON if it ends in `.json`, as TOML otherwise.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = if is_json(path) {
+            serde_json::to_string_pretty(self).map_err(|e| e.to_string())?
+        } else {
+            toml::to_string_pretty(self).map_err(|e| e.to_string())?
+        };
+        std::fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// The config of the dbs loaded with these settings.
+    pub fn config(&self) -> Result<Config, String> {
+        Ok(Config {
+            color: self.color.color_type()?,
+            style: self.style,
+            ..Default::default()
+        })
+    }
+
+    pub fn viewport(&self) -> Option<Rect> {
+        self.viewport
+            .map(|v| Rect::new(Point::new(v[0], v[1]), Point::new(v[2], v[3])))
+    }
+}
+
+/// The `geo_git` directory of the user's config directory.
+pub fn user_dir() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(dir.join("geo_git"))
+}
+
+/// `config.toml`, or `config.json`, in `dir`, if it exists.
+pub fn user_file(dir: &Path) -> Option<PathBuf> {
+    ["config.toml", "config.json"]
+        .into_iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// The project-local config file in `dir` or its nearest ancestor having one.
+pub fn project_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .flat_map(|dir| PROJECT_FILES.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "json")
+}
+
+/// The content of a config file, as JSON whatever its format.
+fn read(path: &Path) -> Result<Value, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let value = if is_json(path) {
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    } else {
+        toml::from_str::<toml::Value>(&text)
+            .map_err(|e| e.to_string())
+            .and_then(|v| serde_json::to_value(v).map_err(|e| e.to_string()))
+    };
+    value.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Override the fields of `base` with those of `over`, table by table.
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(base), Value::Object(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(b) => merge(b, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+#[test]
+fn settings_files() {
+    use crate::geo::color::Color;
+
+    let dir = std::env::temp_dir().join(format!("geo_git_settings_{}", std::process::id()));
+    let nested = dir.join("runs");
+    let user = dir.join("user");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::create_dir_all(&user).unwrap();
+    std::fs::write(
+        user.join("config.toml"),
+        "[keys]\nplay = \"Q\"\nprevious = \"B\"\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("geo_git.toml"),
+        "viewport = [0, 0, 10, 10]\n[color]\nmode = \"categorical\"\nby = \"prefix\"\ndepth = 2\n\
+         [style]\noverlap_color = \"orange\"\n[keys]\nplay = \"P\"\n",
+    )
+    .unwrap();
+    let extra = dir.join("extra.json");
+    std::fs::write(
+        &extra,
+        r#"{ "color": { "palette": "set1" }, "limits": { "max_rate": 60 } }"#,
+    )
+    .unwrap();
+
+    // Fields left out of the files keep their defaults, and the later files add to, or override, the earlier ones.
+    let settings = Settings::load_from(Some(&user), &nested, Some(&extra)).unwrap();
+    assert_eq!(settings.color.mode, ColorMode::Categorical);
+    assert_eq!((settings.color.by, settings.color.depth), (By::Prefix, 2));
+    assert_eq!(settings.color.palette, "set1");
+    assert_eq!(settings.style.overlap_color, Color::rgb(255, 165, 0));
+    assert_eq!(settings.style.density_alpha, 160);
+    assert_eq!(settings.keys.play, "P");
+    assert_eq!(settings.keys.previous, "B");
+    assert_eq!(settings.keys.next, "Right");
+    assert_eq!(settings.limits.max_rate, 60.0);
+    assert_eq!(settings.viewport().unwrap().width(), 10.0);
+    assert!(matches!(
+        settings.config().unwrap().color,
+        ColorType::Categorical {
+            attribute: Attribute::Prefix { depth: 2 },
+            ..
+        }
+    ));
+
+    // Saved settings read back the same, in either format.
+    for name in ["saved.toml", "saved.json"] {
+        let path = dir.join(name);
+        settings.save(&path).unwrap();
+        assert_eq!(
+            Settings::from_value(read(&path).unwrap()),
+            Ok(settings.clone())
+        );
+    }
+
+    // A gradient picked in the GUI is written back in the terms of the command line.
+    let mut color = ColorSettings::default();
+    color.update(&ColorType::Gradient {
+        generator: color::preset("viridis").unwrap(),
+        min: -1.0,
+        max: 1.0,
+        range: ValueRange::Percentile {
+            low: 0.1,
+            high: 0.9,
+        },
+        scale: Scale::Log,
+        diverging: true,
+        channel: "slack".into(),
+    });
+    assert_eq!(color.range, Range::Percentile);
+    assert_eq!(color.percentile, [0.1, 0.9]);
+    assert!(color.log && color.diverging);
+    assert_eq!(color.channel, "slack");
+
+    let typo = serde_json::json!({ "color": { "preset": "magma", "mdoe": "rgb" } });
+    assert!(Settings::from_value(typo).is_err());
+    let unknown = serde_json::json!({ "color": { "preset": "nope" } });
+    assert!(Settings::from_value(unknown).unwrap().config().is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}